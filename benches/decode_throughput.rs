@@ -0,0 +1,147 @@
+// Decode throughput on a big candump file, through the real binary.
+// Run with `cargo bench --bench decode_throughput`, optionally with a frame count:
+// `cargo bench --bench decode_throughput -- 4000000`
+// `-- --baseline <commit>` also builds that commit (in a git worktree under target/) and runs both
+// binaries on the same file, so a change shows up as a before/after pair.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+const DEFAULT_FRAMES: usize = 2_000_000;
+const FRAME_SPACING_S: f64 = 0.000125; // 8000 frames/s, about what a busy car bus does
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let frames = args
+        .iter()
+        .find_map(|a| a.parse().ok())
+        .unwrap_or(DEFAULT_FRAMES);
+    let baseline = args.iter().position(|a| a == "--baseline").map(|i| {
+        args.get(i + 1)
+            .expect("--baseline requires a commit")
+            .clone()
+    });
+
+    let dbc = format!("{}/fs.dbc", env!("CARGO_MANIFEST_DIR"));
+    let log = std::env::temp_dir().join(format!("cantelope_bench_{}.log", frames));
+
+    if !log.exists() {
+        write_log(&dbc, log.to_str().unwrap(), frames);
+    }
+
+    let current = run(env!("CARGO_BIN_EXE_cantelope"), &dbc, log.to_str().unwrap());
+    let Some(rev) = baseline else {
+        report("", frames, current);
+        return;
+    };
+    let before = run(&build(&rev), &dbc, log.to_str().unwrap());
+    report(&format!("{:<10}", rev), frames, before);
+    report(&format!("{:<10}", "current"), frames, current);
+    println!("{:.2}x the frames/s of {}", before / current, rev);
+}
+
+/// Logs the file to Parquet with this binary, returns how long it took in seconds
+fn run(binary: &str, dbc: &str, log: &str) -> f64 {
+    let output = std::env::temp_dir().join("cantelope_bench.parquet");
+    let start = Instant::now();
+    let status = Command::new(binary)
+        .args(["--dbc", dbc, "--candump", "-i", log])
+        .args(["--cache-ms", "10", "--output", output.to_str().unwrap()])
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {}: {}", binary, e));
+    let elapsed = start.elapsed().as_secs_f64();
+    assert!(status.success(), "{} exited with {}", binary, status);
+    _ = fs::remove_file(&output);
+    elapsed
+}
+
+fn report(label: &str, frames: usize, elapsed: f64) {
+    println!(
+        "{}{} frames in {:.2} s: {:.0} frames/s ({:.1}x real time)",
+        label,
+        frames,
+        elapsed,
+        frames as f64 / elapsed,
+        frames as f64 * FRAME_SPACING_S / elapsed
+    );
+}
+
+/// Checks out a commit next to this one and builds its logger in release, returns the binary.
+/// Kept around afterwards so running the comparison again doesn't rebuild it.
+fn build(rev: &str) -> String {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let dir = format!("{}/target/bench-baseline/{}", root, rev.replace('/', "_"));
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .current_dir(root)
+            .args(args)
+            .status()
+            .expect("failed to run git");
+        assert!(status.success(), "git {:?} exited with {}", args, status);
+    };
+    if !Path::new(&dir).exists() {
+        git(&["worktree", "add", "--detach", &dir, rev]);
+    }
+
+    let status = Command::new("cargo")
+        .current_dir(&dir)
+        .args(["build", "--release", "--bin", "cantelope"])
+        .status()
+        .expect("failed to run cargo");
+    assert!(status.success(), "building {} exited with {}", rev, status);
+    format!("{}/target/release/cantelope", dir)
+}
+
+/// Cycles through every message in the DBC that has data, with pseudo random payloads.
+/// Fixed seed so every run (and every commit) decodes the exact same frames.
+fn write_log(dbc: &str, path: &str, frames: usize) {
+    let content = fs::read_to_string(dbc).unwrap();
+    let messages: Vec<(u32, usize)> = content
+        .lines()
+        .filter_map(|line| {
+            // BO_ <id> <name>: <dlc> <node>
+            let mut tokens = line.split_whitespace();
+            if tokens.next() != Some("BO_") {
+                return None;
+            }
+            let id: u32 = tokens.next()?.parse().ok()?;
+            let dlc: usize = tokens.nth(1)?.parse().ok()?;
+            (dlc > 0).then_some((id, dlc))
+        })
+        .collect();
+    assert!(!messages.is_empty(), "no messages in {}", dbc);
+
+    let mut out = BufWriter::new(File::create(path).unwrap());
+    let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+    // First line only sets the start time
+    writeln!(out, "(1700000000.000000) can0 7FF#00").unwrap();
+    for n in 0..frames {
+        let (id, dlc) = messages[n % messages.len()];
+        let timestamp = 1_700_000_000.0 + (n + 1) as f64 * FRAME_SPACING_S;
+        let mut data = String::with_capacity(dlc * 2);
+        for _ in 0..dlc {
+            // xorshift64
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            data.push_str(&format!("{:02X}", seed as u8));
+        }
+        if id > 0x7FF {
+            writeln!(
+                out,
+                "({:.6}) can0 {:08X}#{}",
+                timestamp,
+                id & 0x1FFF_FFFF,
+                data
+            )
+            .unwrap();
+        } else {
+            writeln!(out, "({:.6}) can0 {:03X}#{}", timestamp, id, data).unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
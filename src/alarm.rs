@@ -0,0 +1,195 @@
+// Threshold alarms evaluated on the decoded signal stream
+//
+// Rules live in a plain text file, one per line. Ex:
+// ```
+// # Cell temps
+// BMS_MaxCellTemp > 55 for 500ms
+// inverter_fault == 1
+// ```
+// Supported comparisons are > >= < <= == !=. The optional `for` suffix takes ms or s
+// and means the condition has to hold continuously that long before the alarm goes active.
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Names of the alarms which are currently active, shared with the plot window for the banner
+pub type ActiveAlarms = Arc<Mutex<Vec<String>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn check(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Op::Gt => value > threshold,
+            Op::Ge => value >= threshold,
+            Op::Lt => value < threshold,
+            Op::Le => value <= threshold,
+            // Signals are all f64 after decoding so == has to be a little forgiving
+            Op::Eq => (value - threshold).abs() < 1e-9,
+            Op::Ne => (value - threshold).abs() >= 1e-9,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlarmRule {
+    pub text: String, // The original line, used as the alarm's name everywhere
+    pub signal: String,
+    pub op: Op,
+    pub threshold: f64,
+    pub hold_ms: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AlarmEvent {
+    pub rule: String,
+    pub signal: String,
+    pub start_ms: f64,
+    pub end_ms: Option<f64>, // None if the alarm was still active when logging stopped
+}
+
+#[derive(Default)]
+struct RuleState {
+    pending_since: Option<f64>, // When the condition became true
+    event: Option<usize>,       // Index into events while the alarm is active
+}
+
+pub struct AlarmEngine {
+    rules: Vec<AlarmRule>,
+    states: Vec<RuleState>,
+    pub events: Vec<AlarmEvent>,
+    active: ActiveAlarms,
+    changed: bool, // Something started or cleared since the events were last written
+}
+
+impl AlarmEngine {
+    /// Loads rules from a file, see the top of this file for the format
+    pub fn load(path: &str, active: ActiveAlarms) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut rules = Vec::new();
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            rules.push(parse_rule(line).map_err(|e| format!("{}:{}: {}", path, lineno + 1, e))?);
+        }
+
+        Ok(Self::new(rules, active))
+    }
+
+    pub fn new(rules: Vec<AlarmRule>, active: ActiveAlarms) -> Self {
+        let states = rules.iter().map(|_| RuleState::default()).collect();
+        Self {
+            rules,
+            states,
+            events: Vec::new(),
+            active,
+            changed: false,
+        }
+    }
+
+    /// True once after any alarm started or cleared, so the event file only gets rewritten then
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Feed one decoded value into every rule that references the signal
+    pub fn update(&mut self, signal: &str, time_ms: f64, value: f64) {
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if rule.signal != signal {
+                continue;
+            }
+
+            if rule.op.check(value, rule.threshold) {
+                let since = *state.pending_since.get_or_insert(time_ms);
+                if state.event.is_none() && time_ms - since >= rule.hold_ms {
+                    println!("\nALARM: {} (at {:.0} ms)", rule.text, time_ms);
+                    state.event = Some(self.events.len());
+                    self.events.push(AlarmEvent {
+                        rule: rule.text.clone(),
+                        signal: rule.signal.clone(),
+                        start_ms: time_ms,
+                        end_ms: None,
+                    });
+                    self.changed = true;
+                    if let Ok(mut active) = self.active.lock() {
+                        active.push(rule.text.clone());
+                    }
+                }
+            } else {
+                state.pending_since = None;
+                if let Some(idx) = state.event.take() {
+                    println!("\nCLEARED: {} (at {:.0} ms)", rule.text, time_ms);
+                    self.events[idx].end_ms = Some(time_ms);
+                    self.changed = true;
+                    if let Ok(mut active) = self.active.lock() {
+                        active.retain(|a| a != &rule.text);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn parse_rule(line: &str) -> Result<AlarmRule, String> {
+    // Split off the "for 500ms" part first
+    let (cond, hold_ms) = match line.split_once(" for ") {
+        Some((cond, hold)) => (cond, parse_duration(hold.trim())?),
+        None => (line, 0.0),
+    };
+
+    // Two char operators need to be checked before their one char prefixes
+    let ops = [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        ("==", Op::Eq),
+        ("!=", Op::Ne),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+    ];
+    for (token, op) in ops {
+        if let Some((signal, threshold)) = cond.split_once(token) {
+            let signal = signal.trim();
+            if signal.is_empty() {
+                return Err("missing signal name".to_string());
+            }
+            let threshold = threshold
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("bad threshold '{}'", threshold.trim()))?;
+            return Ok(AlarmRule {
+                text: line.to_string(),
+                signal: signal.to_string(),
+                op,
+                threshold,
+                hold_ms,
+            });
+        }
+    }
+
+    Err("no comparison operator found".to_string())
+}
+
+fn parse_duration(s: &str) -> Result<f64, String> {
+    let (num, scale) = if let Some(num) = s.strip_suffix("ms") {
+        (num, 1.0)
+    } else if let Some(num) = s.strip_suffix('s') {
+        (num, 1000.0)
+    } else {
+        (s, 1.0) // Bare numbers are ms
+    };
+    num.trim()
+        .parse::<f64>()
+        .map(|n| n * scale)
+        .map_err(|_| format!("bad duration '{}'", s))
+}
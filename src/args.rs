@@ -1,5 +1,9 @@
 use std::env;
 
+use crate::player::PlayOptions;
+use crate::replay::ReplayOptions;
+use crate::transmit::SendOptions;
+
 #[derive(Default)]
 pub enum CanDataInput {
     #[default]
@@ -7,19 +11,71 @@ pub enum CanDataInput {
     Socket,
     Stdin,
     Remote,
+    Replay,   // A log played back with its original timing, see replay.rs
+    Simulate, // Made up traffic from the DBC, see simulate.rs
+}
+
+/// How signals get turned into column names, see schema::Naming
+#[derive(Default, Clone, PartialEq)]
+pub enum NamingPolicy {
+    #[default]
+    Plain, // Just the signal name
+    Message,   // Always Message.Signal
+    Collision, // Message.Signal only for names that appear in more than one message
+}
+
+/// Which value a column keeps when a signal shows up more than once in a chunk
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    #[default]
+    First,
+    Last,
+    Min,
+    Max,
+}
+
+#[derive(Default, PartialEq)]
+pub enum Mode {
+    #[default]
+    Log,
+    CheckDbc, // `cantelope check-dbc a.dbc ...`
+    Play,     // `cantelope play -i run.log --bus vcan0=can0`, see player.rs
+    Send,     // `cantelope send --dbc x.dbc MSG SIG=1 --out can0`, see transmit.rs
+    View,     // `cantelope view run.parquet`, see viewer.rs
+    Export,   // `cantelope export run.parquet --plot a,b -o summary.png`, see export.rs
 }
 
 #[derive(Default)]
 pub struct Args {
-    pub dbcfile: String,
+    pub mode: Mode,
+    pub dbcfiles: Vec<String>, // --dbc can be given more than once, they get merged
     pub input: String,
     pub output: String,
     pub candatainput: CanDataInput,
     pub cache_ms: f64,
     pub aux_outputs: Vec<String>,
     pub plots: Vec<Vec<String>>,
+    pub xy_plots: Vec<Vec<String>>, // X, Y and maybe color for each --xy
+    pub window_ms: f64,             // Plot window length, 0 is the default 10 s
     pub en_ipm: bool,
     pub en_aux: bool,
+    pub alarmfile: String,               // Empty if no alarms
+    pub alarm_output: String,            // Defaults to <output>_alarms.parquet
+    pub derivedfile: String,             // Empty if no derived signals
+    pub dashboard_file: String,          // Empty if no dashboard
+    pub workspace_file: String,          // Empty if no saved layout
+    pub export_file: String,             // Plots drawn here once logging is done, empty for none
+    pub export_size: Option<(u32, u32)>, // Picture size for exports, None sizes it by pane count
+    pub no_gui: bool,                    // No plot window, for --export without a display
+    pub watch_dbc: bool,                 // Reload the DBC when it changes
+    pub stream_rows: usize,              // 0 keeps everything in memory until the end
+    pub naming: NamingPolicy,
+    pub rename_file: String, // Empty if no renames
+    pub aggregate: Aggregation,
+    pub replay: ReplayOptions,
+    pub play: PlayOptions,
+    pub send: SendOptions,
+    pub seed: u64, // For --simulate's random walks
 }
 
 pub fn process_args() -> Args {
@@ -31,7 +87,7 @@ pub fn process_args() -> Args {
         match arg.as_str() {
             "--dbc" | "-d" => {
                 let value = argsi.next().expect("--dbc requires a value");
-                args.dbcfile = value;
+                args.dbcfiles.push(value);
             }
 
             "--input" | "-i" => {
@@ -61,6 +117,26 @@ pub fn process_args() -> Args {
                 args.candatainput = CanDataInput::Remote;
             }
 
+            "--replay" => {
+                args.candatainput = CanDataInput::Replay;
+            }
+
+            "--simulate" => {
+                args.candatainput = CanDataInput::Simulate;
+            }
+
+            "--seed" => {
+                args.seed = argsi
+                    .next()
+                    .expect("--seed requires a value")
+                    .parse()
+                    .unwrap();
+            }
+
+            "--speed" | "--loop" | "--start" | "--end" => {
+                args.replay.parse_arg(&arg, &mut argsi);
+            }
+
             "--cache-ms" | "-c" => {
                 args.cache_ms = argsi
                     .next()
@@ -88,11 +164,157 @@ pub fn process_args() -> Args {
                 args.en_aux = true;
             }
 
+            "--xy" => {
+                let raw_val = argsi.next().expect("--xy requires a value");
+                let list: Vec<String> = raw_val.split(',').map(|s| s.to_string()).collect();
+                if !(2..=3).contains(&list.len()) {
+                    panic!("--xy expects X,Y or X,Y,COLOR");
+                }
+                args.aux_outputs.extend(list.clone());
+                args.xy_plots.push(list);
+                args.en_aux = true;
+            }
+
+            "--gui" => {
+                args.en_aux = true; // Plot window with no panes yet, pick signals in it
+            }
+
+            "--window-ms" => {
+                args.window_ms = argsi
+                    .next()
+                    .expect("--window-ms requires a value")
+                    .parse()
+                    .unwrap();
+            }
+
+            "--alarms" | "-a" => {
+                args.alarmfile = argsi.next().expect("--alarms requires a value");
+            }
+
+            "--derived" | "-e" => {
+                args.derivedfile = argsi.next().expect("--derived requires a value");
+            }
+
+            "--dashboard" => {
+                args.dashboard_file = argsi.next().expect("--dashboard requires a value");
+                args.en_aux = true; // Lives in the plot window
+            }
+
+            "--workspace" => {
+                args.workspace_file = argsi.next().expect("--workspace requires a value");
+                args.en_aux = true;
+            }
+
+            "--watch-dbc" | "-w" => {
+                args.watch_dbc = true;
+            }
+
+            "--stream-rows" => {
+                args.stream_rows = argsi
+                    .next()
+                    .expect("--stream-rows requires a value")
+                    .parse()
+                    .unwrap();
+            }
+
+            "--naming" | "-n" => {
+                args.naming = match argsi.next().expect("--naming requires a value").as_str() {
+                    "plain" => NamingPolicy::Plain,
+                    "message" => NamingPolicy::Message,
+                    "collision" => NamingPolicy::Collision,
+                    other => panic!(
+                        "Unknown naming policy {}, use plain, message or collision",
+                        other
+                    ),
+                };
+            }
+
+            "--rename" => {
+                args.rename_file = argsi.next().expect("--rename requires a value");
+            }
+
+            "--aggregate" => {
+                args.aggregate = match argsi.next().expect("--aggregate requires a value").as_str()
+                {
+                    "first" => Aggregation::First,
+                    "last" => Aggregation::Last,
+                    "min" => Aggregation::Min,
+                    "max" => Aggregation::Max,
+                    other => panic!("Unknown aggregation {}, use first, last, min or max", other),
+                };
+            }
+
+            "--alarm-output" => {
+                args.alarm_output = argsi.next().expect("--alarm-output requires a value");
+            }
+
+            "check-dbc" if args.mode == Mode::Log => {
+                args.mode = Mode::CheckDbc;
+            }
+
+            "play" if args.mode == Mode::Log => {
+                args.mode = Mode::Play;
+            }
+
+            "--bus" | "--remap" | "--only" | "--skip" | "--time-offset" => {
+                args.play.parse_arg(&arg, &mut argsi);
+            }
+
+            "send" if args.mode == Mode::Log => {
+                args.mode = Mode::Send;
+            }
+
+            "view" if args.mode == Mode::Log => {
+                args.mode = Mode::View;
+            }
+
+            "export" if args.mode == Mode::Log => {
+                args.mode = Mode::Export;
+            }
+
+            "--export" => {
+                args.export_file = argsi.next().expect("--export requires a value");
+            }
+
+            "--no-gui" => {
+                args.no_gui = true;
+            }
+
+            "--size" => {
+                let value = argsi.next().expect("--size requires a value");
+                let (width, height) = value.split_once('x').expect("--size expects WIDTHxHEIGHT");
+                args.export_size = Some((width.parse().unwrap(), height.parse().unwrap()));
+            }
+
+            "--period-ms" | "--count" | "--out" | "--serve" => {
+                args.send.parse_arg(&arg, &mut argsi);
+            }
+
+            // check-dbc takes its files without --dbc too
+            _ if args.mode == Mode::CheckDbc && !arg.starts_with('-') => {
+                args.dbcfiles.push(arg);
+            }
+
+            // view and export take their file without -i too
+            _ if matches!(args.mode, Mode::View | Mode::Export) && !arg.starts_with('-') => {
+                args.input = arg;
+            }
+
+            // send's message and SIGNAL=value list
+            _ if args.mode == Mode::Send && !arg.starts_with('-') => {
+                args.send.positional(arg);
+            }
+
             _ => {
                 eprintln!("Unknown argument: {}", arg);
             }
         }
     }
 
+    if !args.alarmfile.is_empty() && args.alarm_output.is_empty() && args.en_ipm {
+        let stem = args.output.strip_suffix(".parquet").unwrap_or(&args.output);
+        args.alarm_output = format!("{}_alarms.parquet", stem);
+    }
+
     return args;
 }
@@ -0,0 +1,33 @@
+// Where a signal's bits live in the frame, as byte * 8 + bit_in_byte indexes
+
+/// Intel signals count up from the start bit. Motorola ones start at their MSB
+/// and zig-zag through the bytes (the usual DBC "sawtooth" numbering).
+pub fn signal_bits(start: usize, len: usize, big_endian: bool) -> Vec<usize> {
+    if !big_endian {
+        return (start..start + len).collect();
+    }
+
+    let mut bits = Vec::with_capacity(len);
+    let mut pos = start;
+    for _ in 0..len {
+        bits.push(pos);
+        if pos % 8 == 0 {
+            pos += 15; // Wrap to the MSB of the next byte
+        } else {
+            pos -= 1;
+        }
+    }
+    bits
+}
+
+/// The raw value of a signal from the bit indexes signal_bits() gave, MSB first for Motorola.
+/// Bits past the end of a short frame read as 0.
+pub fn raw_value(data: &[u8], bits: &[usize], big_endian: bool) -> u64 {
+    let mut raw = 0u64;
+    for (i, &pos) in bits.iter().enumerate() {
+        let bit = data.get(pos / 8).map_or(0, |b| (b >> (pos % 8)) & 1) as u64;
+        let shift = if big_endian { bits.len() - 1 - i } else { i };
+        raw |= bit << shift;
+    }
+    raw
+}
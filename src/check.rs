@@ -0,0 +1,153 @@
+// `cantelope check-dbc a.dbc [b.dbc ...]`: looks for problems in DBCs without logging anything.
+// Errors are things that would make the logged data wrong, warnings are just suspicious.
+
+use dbc_rs::{ByteOrder, Dbc};
+use std::collections::HashMap;
+
+use crate::FloatExt;
+use crate::bits::signal_bits;
+use crate::dbcextra::DbcExtras;
+use crate::schema::{self, Naming, column_type, physical_range};
+
+/// Returns false if anything counted as an error. Duplicate names are only errors if `naming`
+/// (--naming and --rename) doesn't give them separate columns.
+pub fn run(paths: &[String], naming: &Naming) -> bool {
+    let sources = match schema::read_sources(paths) {
+        Ok(s) => s,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return false;
+        }
+    };
+
+    let mut errors = 0;
+    let mut warnings = 0;
+    let mut signal_owner: HashMap<String, (String, String)> = HashMap::new(); // Name -> (path, message)
+
+    for (path, content) in &sources {
+        println!("{}", path);
+        let dbc = match Dbc::parse(content) {
+            Ok(dbc) => dbc,
+            Err(e) => {
+                println!("  ERROR: failed to parse: {}", e);
+                errors += 1;
+                continue;
+            }
+        };
+        let extras = DbcExtras::parse(content);
+
+        for message in dbc.messages().iter() {
+            println!(
+                "  BO_ {} {} (dlc {})",
+                message.id(),
+                message.name(),
+                message.dlc()
+            );
+            let mux = extras.mux_for(message.id());
+            let frame_bits = message.dlc() as usize * 8;
+            let mut used: Vec<(String, Vec<usize>)> = Vec::new(); // Bits of each signal checked so far
+
+            for signal in message.signals().iter() {
+                let float = extras.is_float(message.id(), signal.name());
+                let (data_type, _) = column_type(signal, float);
+                println!("    {:<40} {}", signal.name(), data_type);
+
+                let big_endian = matches!(signal.byte_order(), ByteOrder::BigEndian);
+                let bits = signal_bits(
+                    signal.start_bit() as usize,
+                    signal.length() as usize,
+                    big_endian,
+                );
+
+                // Runs past the DLC
+                if let Some(max) = bits.iter().max()
+                    && *max >= frame_bits
+                {
+                    println!(
+                        "      ERROR: uses bit {} but the message is only {} bytes",
+                        max,
+                        message.dlc()
+                    );
+                    errors += 1;
+                }
+
+                // Overlaps another signal. Different pages of the same switch are allowed to.
+                for (other, other_bits) in &used {
+                    let shared = bits.iter().filter(|b| other_bits.contains(b)).count();
+                    if shared == 0 {
+                        continue;
+                    }
+                    let exclusive = mux.is_some_and(|m| {
+                        match (m.conditions.get(signal.name()), m.conditions.get(other)) {
+                            (Some(a), Some(b)) => {
+                                a.switch != b.switch
+                                    || !a.ranges.iter().any(|&(alo, ahi)| {
+                                        b.ranges.iter().any(|&(blo, bhi)| alo <= bhi && blo <= ahi)
+                                    })
+                            }
+                            _ => false,
+                        }
+                    });
+                    if !exclusive {
+                        println!("      ERROR: shares {} bit(s) with {}", shared, other);
+                        errors += 1;
+                    }
+                }
+                used.push((signal.name().to_string(), bits));
+
+                // Same name as a signal somewhere else, which collides unless naming splits them up
+                let owner = (path.clone(), message.name().to_string());
+                if let Some((other_path, other_message)) =
+                    signal_owner.insert(signal.name().to_string(), owner)
+                {
+                    let column = naming.column_name(message.name(), signal.name(), 2);
+                    let other_column = naming.column_name(&other_message, signal.name(), 2);
+                    if column != other_column {
+                        println!(
+                            "      WARNING: name is also used in {} ({}), logged as {} and {}",
+                            other_path, other_message, other_column, column
+                        );
+                        warnings += 1;
+                    } else {
+                        println!(
+                            "      ERROR: name is also used in {} ({}) (log with --naming or --rename to keep both)",
+                            other_path, other_message
+                        );
+                        errors += 1;
+                    }
+                }
+
+                // Declared range vs what the raw bits can actually produce. [0|0] means "not specified".
+                // IEEE floats cover about any range, so there's nothing to check for them.
+                let unspecified = signal.min().is_nearly(0.0) && signal.max().is_nearly(0.0);
+                if !float
+                    && let Some((lo, hi)) = physical_range(
+                        signal.length() as u32,
+                        signal.is_unsigned(),
+                        signal.factor(),
+                        signal.offset(),
+                    )
+                    && !unspecified
+                {
+                    let slack = signal.factor().abs(); // One LSB of rounding is normal
+                    if signal.min() < lo - slack || signal.max() > hi + slack {
+                        println!(
+                            "      WARNING: range [{}|{}] doesn't fit in {} bits x {} + {} = [{}|{}]",
+                            signal.min(),
+                            signal.max(),
+                            signal.length(),
+                            signal.factor(),
+                            signal.offset(),
+                            lo,
+                            hi
+                        );
+                        warnings += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    println!("{} error(s), {} warning(s)", errors, warnings);
+    errors == 0
+}
@@ -0,0 +1,321 @@
+// Pit wall widgets above the plots: big numbers, needle gauges, bar graphs and lamps.
+//
+// Declared in a plain text file, one widget per line, `row` starts a new row. Ex:
+// ```
+// readout BMS_PackVoltage
+// gauge SME_TRQSPD_Speed 0 6000
+// lamp SME_THROTL_PowerReady green
+// lamp BMS_Fault red
+// row
+// bars BMS_Cell1,BMS_Cell2,BMS_Cell3,BMS_Cell4 2.8 4.2
+// ```
+// Gauge and bar ranges are optional and default to the DBC's [min|max]. Lamps are lit when
+// the signal isn't 0, in green (default), red or amber.
+
+use std::collections::HashMap;
+use std::fs;
+
+use iced::widget::{Column, Row, column, container, text};
+use iced::{Color, Element, Length};
+use plotters::prelude::*;
+use plotters_iced2::{Chart, ChartBuilder, ChartWidget};
+
+use crate::dbcextra::describe;
+use crate::plot::Message;
+use crate::plotbuf::PlotStore;
+use crate::schema::SignalInfo;
+
+const WIDGET_HEIGHT: f32 = 180.0;
+
+#[derive(Debug, Clone)]
+pub enum Widget {
+    Readout(String),
+    Gauge(String, Option<(f64, f64)>),
+    Bars(Vec<String>, Option<(f64, f64)>),
+    Lamp(String, [f32; 3]),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dashboard {
+    pub rows: Vec<Vec<Widget>>,
+}
+
+impl Dashboard {
+    /// Loads a dashboard file, see the top of this file for the format
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut rows = vec![Vec::new()];
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |e: &str| format!("{}:{}: {}", path, lineno + 1, e);
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            // Optional `min max` after the signal(s), both or neither
+            let range = |rest: &[&str]| -> Result<Option<(f64, f64)>, String> {
+                match rest {
+                    [] => Ok(None),
+                    [lo, hi] => Ok(Some((
+                        lo.parse().map_err(|_| err("min isn't a number"))?,
+                        hi.parse().map_err(|_| err("max isn't a number"))?,
+                    ))),
+                    _ => Err(err("expected `min max` after the signal, or nothing")),
+                }
+            };
+
+            let widget = match tokens.as_slice() {
+                ["row"] => {
+                    rows.push(Vec::new());
+                    continue;
+                }
+                ["readout", signal] => Widget::Readout(signal.to_string()),
+                ["readout", ..] => return Err(err("readout takes one signal")),
+                ["gauge", signal, rest @ ..] => Widget::Gauge(signal.to_string(), range(rest)?),
+                ["bars", signals, rest @ ..] => Widget::Bars(
+                    signals.split(',').map(|s| s.to_string()).collect(),
+                    range(rest)?,
+                ),
+                ["lamp", signal] => Widget::Lamp(signal.to_string(), [0.1, 0.8, 0.2]),
+                ["lamp", signal, color] => {
+                    let color = match *color {
+                        "green" => [0.1, 0.8, 0.2],
+                        "red" => [0.9, 0.1, 0.1],
+                        "amber" => [1.0, 0.65, 0.0],
+                        other => return Err(err(&format!("unknown lamp color {}", other))),
+                    };
+                    Widget::Lamp(signal.to_string(), color)
+                }
+                ["lamp", ..] => return Err(err("lamp takes a signal and an optional color")),
+                ["row", ..] => return Err(err("row doesn't take anything")),
+                ["gauge" | "bars"] => return Err(err("missing the signal")),
+                _ => return Err(err("expected readout, gauge, bars, lamp or row")),
+            };
+            rows.last_mut().unwrap().push(widget);
+        }
+        rows.retain(|r| !r.is_empty());
+        Ok(Self { rows })
+    }
+
+    /// Every signal the widgets need, so the data thread sends them over
+    pub fn signals(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for widget in self.rows.iter().flatten() {
+            match widget {
+                Widget::Readout(s) | Widget::Gauge(s, _) | Widget::Lamp(s, _) => {
+                    out.push(s.clone())
+                }
+                Widget::Bars(signals, _) => out.extend(signals.iter().cloned()),
+            }
+        }
+        out
+    }
+
+    pub fn view<'a>(
+        &'a self,
+        store: &PlotStore,
+        info: &'a HashMap<String, SignalInfo>,
+        at: Option<f64>, // Latest values if None
+    ) -> Element<'a, Message> {
+        let latest = |name: &str| {
+            let ring = store.get(name)?;
+            let ring = ring.lock().unwrap();
+            match at {
+                Some(x) => ring.value_at(x),
+                None => ring.last().map(|(_, y)| y),
+            }
+        };
+        // Explicit range, or the DBC's, or 0 to 100 if nobody says
+        let range = |given: Option<(f64, f64)>, names: &[String]| {
+            given
+                .or_else(|| {
+                    names
+                        .iter()
+                        .filter_map(|n| info.get(n).and_then(|i| i.range))
+                        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+                })
+                .unwrap_or((0.0, 100.0))
+        };
+
+        let mut rows = Column::new().spacing(10);
+        for widgets in &self.rows {
+            let mut row = Row::new().spacing(10).height(WIDGET_HEIGHT);
+            for widget in widgets {
+                let element: Element<Message> = match widget {
+                    Widget::Readout(name) => {
+                        let value = latest(name);
+                        let shown = match (value, info.get(name).and_then(|i| i.values.as_ref())) {
+                            (Some(v), Some(table)) => describe(table, v).unwrap_or("?").to_string(),
+                            (Some(v), None) => format!("{:.2} {}", v, unit(info, name)),
+                            (None, _) => "-".to_string(),
+                        };
+                        container(column![text(name.as_str()).size(14), text(shown).size(44)])
+                            .padding(10)
+                            .into()
+                    }
+                    Widget::Gauge(name, given) => ChartWidget::new(Gauge {
+                        name,
+                        unit: unit(info, name),
+                        value: latest(name),
+                        range: range(*given, std::slice::from_ref(name)),
+                    })
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+                    Widget::Bars(names, given) => ChartWidget::new(Bars {
+                        values: names.iter().map(|n| latest(n)).collect(),
+                        range: range(*given, names),
+                        unit: names.first().map(|n| unit(info, n)).unwrap_or(""),
+                    })
+                    .width(Length::FillPortion(3))
+                    .height(Length::Fill)
+                    .into(),
+                    Widget::Lamp(name, [r, g, b]) => {
+                        let background = match latest(name) {
+                            Some(v) if v.abs() > 1e-9 => Color::from_rgb(*r, *g, *b),
+                            Some(_) => Color::from_rgb(0.25, 0.25, 0.25),
+                            None => Color::from_rgb(0.6, 0.6, 0.6), // Nothing received yet
+                        };
+                        container(text(name.as_str()).size(16).color(Color::WHITE))
+                            .padding(10)
+                            .center(Length::Fill)
+                            .style(move |_| container::background(background))
+                            .into()
+                    }
+                };
+                row = row.push(element);
+            }
+            rows = rows.push(row);
+        }
+        rows.into()
+    }
+}
+
+fn unit<'a>(info: &'a HashMap<String, SignalInfo>, name: &str) -> &'a str {
+    info.get(name).map(|i| i.unit.as_str()).unwrap_or("")
+}
+
+struct Gauge<'a> {
+    name: &'a str,
+    unit: &'a str,
+    value: Option<f64>,
+    range: (f64, f64),
+}
+
+impl Chart<Message> for Gauge<'_> {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        // Unit circle coordinates, the dial is the top half
+        let mut chart = builder
+            .margin(5)
+            .build_cartesian_2d(-1.2f64..1.2f64, -0.45f64..1.15f64)
+            .unwrap();
+
+        let (lo, hi) = self.range;
+        let angle = |v: f64| std::f64::consts::PI * (1.0 - ((v - lo) / (hi - lo)).clamp(0.0, 1.0));
+        let arc = |from: f64, to: f64| {
+            (0..=60).map(move |i| {
+                let a = angle(from + (to - from) * f64::from(i) / 60.0);
+                (a.cos(), a.sin())
+            })
+        };
+
+        chart
+            .draw_series(LineSeries::new(arc(lo, hi), BLACK.mix(0.2).stroke_width(8)))
+            .unwrap();
+        if let Some(value) = self.value {
+            chart
+                .draw_series(LineSeries::new(arc(lo, value), BLUE.stroke_width(8)))
+                .unwrap();
+            let a = angle(value);
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(0.0, 0.0), (0.85 * a.cos(), 0.85 * a.sin())],
+                    RED.stroke_width(4),
+                )))
+                .unwrap();
+        }
+
+        let shown = match self.value {
+            Some(v) => format!("{:.1} {}", v, self.unit),
+            None => "-".to_string(),
+        };
+        let labels = [
+            (format!("{}", lo), (-1.15, -0.05), 14),
+            (format!("{}", hi), (0.9, -0.05), 14),
+            (shown, (-0.3, -0.1), 22),
+            (self.name.to_string(), (-1.15, 1.12), 14),
+        ];
+        chart
+            .draw_series(
+                labels
+                    .into_iter()
+                    .map(|(label, pos, size)| Text::new(label, pos, ("sans-serif", size))),
+            )
+            .unwrap();
+    }
+}
+
+struct Bars<'a> {
+    values: Vec<Option<f64>>,
+    range: (f64, f64),
+    unit: &'a str,
+}
+
+impl Chart<Message> for Bars<'_> {
+    type State = ();
+
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        let known: Vec<f64> = self.values.iter().flatten().copied().collect();
+        let min = known.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = known.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        // Spread is what matters for cell balancing, so it goes in the caption
+        let caption = if known.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "min {:.3}  max {:.3}  spread {:.3} {}",
+                min,
+                max,
+                max - min,
+                self.unit
+            )
+        };
+
+        let (lo, hi) = self.range;
+        let mut chart = builder
+            .margin(5)
+            .caption(caption, ("sans-serif", 14))
+            .y_label_area_size(45)
+            .build_cartesian_2d(0f64..self.values.len() as f64, lo..hi)
+            .unwrap();
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .disable_x_axis()
+            .y_desc(self.unit)
+            .draw()
+            .unwrap();
+
+        // Lowest one red, highest one green, so the outliers jump out
+        let bars = self.values.iter().enumerate().filter_map(|(i, v)| {
+            let v = (*v)?;
+            let color = if v == min {
+                RED
+            } else if v == max {
+                GREEN
+            } else {
+                BLUE
+            };
+            let x = i as f64;
+            Some(Rectangle::new(
+                [(x + 0.1, lo), (x + 0.9, v.clamp(lo, hi))],
+                color.filled(),
+            ))
+        });
+        chart.draw_series(bars).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+// Bits of the DBC that dbc_rs doesn't hand us, pulled straight out of the file text.
+// Only looks at the statements it cares about, everything else is left to Dbc::parse.
+
+use std::collections::{HashMap, HashSet};
+
+/// Raw value -> description, in the order the DBC lists them
+pub type ValueTable = Vec<(i64, String)>;
+
+/// When a multiplexed signal is actually present in a frame
+#[derive(Debug, Clone)]
+pub struct MuxCondition {
+    pub switch: String,          // Name of the multiplexor signal
+    pub ranges: Vec<(i64, i64)>, // Inclusive switch value ranges where the signal is present
+}
+
+/// Multiplexing of one message. Signals not in here are always present.
+#[derive(Default, Debug, Clone)]
+pub struct MessageMux {
+    pub conditions: HashMap<String, MuxCondition>,
+}
+
+impl MessageMux {
+    /// `value_of` looks up a signal's decoded value in the current frame.
+    /// Nested switches (extended multiplexing) have to be active themselves for their pages to count.
+    pub fn is_active(&self, signal: &str, value_of: &impl Fn(&str) -> Option<f64>) -> bool {
+        let mut name = signal;
+        // Bounded so a cycle in a broken DBC can't hang the decode loop
+        for _ in 0..16 {
+            let Some(cond) = self.conditions.get(name) else {
+                return true;
+            };
+            let Some(switch) = value_of(&cond.switch) else {
+                return false;
+            };
+            let switch = switch.round() as i64;
+            if !cond
+                .ranges
+                .iter()
+                .any(|&(lo, hi)| (lo..=hi).contains(&switch))
+            {
+                return false;
+            }
+            name = &cond.switch;
+        }
+        false
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct DbcExtras {
+    // (message id, signal name) -> value table
+    pub value_tables: HashMap<(u32, String), ValueTable>,
+    // message id -> multiplexing, only for messages that have any
+    pub mux: HashMap<u32, MessageMux>,
+    // message id -> GenMsgCycleTime in ms, and the attribute's default for the rest
+    pub cycle_times: HashMap<u32, u32>,
+    pub default_cycle_time: Option<u32>,
+    // message id -> node that sends it (the last thing on the BO_ line)
+    pub senders: HashMap<u32, String>,
+    // (message id, signal name) -> unit, only for signals that have one
+    pub units: HashMap<(u32, String), String>,
+    // (message id, signal name) of IEEE float signals (SIG_VALTYPE_ 1 or 2)
+    pub floats: HashSet<(u32, String)>,
+}
+
+impl DbcExtras {
+    pub fn parse(content: &str) -> Self {
+        let mut extras = Self::default();
+
+        // Plain M / mN multiplexing is on the SG_ lines themselves
+        let mut current_id = None;
+        let mut switches: HashMap<u32, String> = HashMap::new();
+        let mut pages: Vec<(u32, String, i64)> = Vec::new();
+        for line in content.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("BO_") => {
+                    current_id = tokens.next().and_then(|id| id.parse::<u32>().ok());
+                    // BO_ <id> <name>: <dlc> <sender>
+                    if let (Some(id), Some(sender)) = (current_id, tokens.nth(2)) {
+                        extras.senders.insert(raw_id(id), sender.to_string());
+                    }
+                }
+                Some("SG_") => {
+                    let (Some(id), Some(name), Some(indicator)) =
+                        (current_id, tokens.next(), tokens.next())
+                    else {
+                        continue;
+                    };
+                    // SG_ <name> [mux] : <bits> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>
+                    let mut quoted = line.split('"');
+                    if let Some(unit) = quoted.nth(1)
+                        && !unit.is_empty()
+                    {
+                        extras
+                            .units
+                            .insert((raw_id(id), name.to_string()), unit.to_string());
+                    }
+                    // Both "mNM" (multiplexed switch) and "M" can be switches, only "M" is the top level one
+                    if indicator == "M" {
+                        switches.insert(raw_id(id), name.to_string());
+                    }
+                    if let Some(page) = indicator.strip_prefix('m') {
+                        let page = page.trim_end_matches('M');
+                        if let Ok(page) = page.parse::<i64>() {
+                            pages.push((raw_id(id), name.to_string(), page));
+                        }
+                    }
+                }
+                Some(t) if !t.is_empty() => current_id = None,
+                _ => {}
+            }
+        }
+        for (id, name, page) in pages {
+            if let Some(switch) = switches.get(&id) {
+                extras.mux.entry(id).or_default().conditions.insert(
+                    name,
+                    MuxCondition {
+                        switch: switch.clone(),
+                        ranges: vec![(page, page)],
+                    },
+                );
+            }
+        }
+
+        for statement in statements(content) {
+            let tokens = tokenize(&statement);
+            // VAL_ <msg id> <signal> <value> "<desc>" ... ;
+            if tokens.first().map(|s| s.as_str()) == Some("VAL_") && tokens.len() >= 3 {
+                let Ok(id) = tokens[1].parse::<u32>() else {
+                    continue; // Environment variable tables don't have an id
+                };
+                let mut table = ValueTable::new();
+                for pair in tokens[3..].chunks(2) {
+                    if let [value, desc] = pair
+                        && let Ok(value) = value.parse::<i64>()
+                    {
+                        table.push((value, desc.clone()));
+                    }
+                }
+                extras
+                    .value_tables
+                    .insert((raw_id(id), tokens[2].clone()), table);
+            }
+
+            // Extended multiplexing, overrides whatever the SG_ line said
+            // SG_MUL_VAL_ <msg id> <signal> <switch> <lo>-<hi>, <lo>-<hi> ;
+            if tokens.first().map(|s| s.as_str()) == Some("SG_MUL_VAL_") && tokens.len() >= 4 {
+                let Ok(id) = tokens[1].parse::<u32>() else {
+                    continue;
+                };
+                let ranges = tokens[4..]
+                    .iter()
+                    .filter_map(|r| {
+                        let (lo, hi) = r.trim_matches(',').split_once('-')?;
+                        Some((lo.parse().ok()?, hi.parse().ok()?))
+                    })
+                    .collect();
+                extras.mux.entry(raw_id(id)).or_default().conditions.insert(
+                    tokens[2].clone(),
+                    MuxCondition {
+                        switch: tokens[3].clone(),
+                        ranges,
+                    },
+                );
+            }
+
+            // SIG_VALTYPE_ <msg id> <signal> : <1 = float, 2 = double> ;
+            if tokens.first().map(|s| s.as_str()) == Some("SIG_VALTYPE_")
+                && tokens.len() >= 4
+                && let Ok(id) = tokens[1].parse::<u32>()
+                && matches!(
+                    tokens.last().map(|s| s.trim_start_matches(':')),
+                    Some("1" | "2")
+                )
+            {
+                let name = tokens[2].trim_end_matches(':').to_string();
+                extras.floats.insert((raw_id(id), name));
+            }
+
+            // BA_ "GenMsgCycleTime" BO_ <msg id> <ms> ;
+            if tokens.len() == 5
+                && tokens[0] == "BA_"
+                && tokens[1] == "GenMsgCycleTime"
+                && tokens[2] == "BO_"
+                && let (Ok(id), Ok(ms)) = (tokens[3].parse::<u32>(), tokens[4].parse::<u32>())
+            {
+                extras.cycle_times.insert(raw_id(id), ms);
+            }
+            // BA_DEF_DEF_ "GenMsgCycleTime" <ms> ;
+            if tokens.len() == 3
+                && tokens[0] == "BA_DEF_DEF_"
+                && tokens[1] == "GenMsgCycleTime"
+                && let Ok(ms) = tokens[2].parse::<u32>()
+            {
+                extras.default_cycle_time = Some(ms);
+            }
+        }
+
+        extras
+    }
+
+    pub fn value_table(&self, id: u32, signal: &str) -> Option<&ValueTable> {
+        self.value_tables.get(&(raw_id(id), signal.to_string()))
+    }
+
+    /// Folds another file's extras into this one
+    pub fn merge(&mut self, other: DbcExtras) {
+        self.value_tables.extend(other.value_tables);
+        self.mux.extend(other.mux);
+        self.cycle_times.extend(other.cycle_times);
+        self.default_cycle_time = self.default_cycle_time.or(other.default_cycle_time);
+        self.senders.extend(other.senders);
+        self.units.extend(other.units);
+        self.floats.extend(other.floats);
+    }
+
+    pub fn is_float(&self, id: u32, signal: &str) -> bool {
+        self.floats.contains(&(raw_id(id), signal.to_string()))
+    }
+
+    pub fn mux_for(&self, id: u32) -> Option<&MessageMux> {
+        self.mux.get(&raw_id(id))
+    }
+}
+
+/// Look up the description for a decoded value
+pub fn describe(table: &ValueTable, value: f64) -> Option<&str> {
+    let raw = value.round() as i64;
+    table
+        .iter()
+        .find(|(v, _)| *v == raw)
+        .map(|(_, d)| d.as_str())
+}
+
+/// DBC files mark extended ids by setting bit 31, strip it so everything compares the same
+pub fn raw_id(id: u32) -> u32 {
+    id & 0x1FFF_FFFF
+}
+
+/// Splits the file into `;` terminated statements for the keywords we care about.
+/// Statements can span lines, and `;` inside quotes doesn't count.
+fn statements(content: &str) -> Vec<String> {
+    const KEYWORDS: [&str; 5] = [
+        "VAL_ ",
+        "SG_MUL_VAL_ ",
+        "BA_ ",
+        "BA_DEF_DEF_ ",
+        "SIG_VALTYPE_ ",
+    ];
+
+    let mut out = Vec::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if current.is_none() && KEYWORDS.iter().any(|k| trimmed.starts_with(k)) {
+            current = Some(String::new());
+        }
+        if let Some(stmt) = current.as_mut() {
+            stmt.push_str(trimmed);
+            stmt.push(' ');
+
+            let mut in_quotes = false;
+            let done = stmt.chars().any(|c| {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                c == ';' && !in_quotes
+            });
+            if done {
+                out.push(current.take().unwrap());
+            }
+        }
+    }
+    out
+}
+
+/// Whitespace separated tokens, with quoted strings kept whole (quotes removed) and the trailing ; dropped
+fn tokenize(statement: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in statement.chars() {
+        match c {
+            '"' => {
+                if in_quotes {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                in_quotes = !in_quotes;
+            }
+            _ if in_quotes => current.push(c),
+            ';' => break,
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
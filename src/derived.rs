@@ -0,0 +1,457 @@
+// Virtual signals computed from decoded DBC signals
+//
+// Defined in a plain text file, one per line, `name = expression`. Ex:
+// ```
+// Power_kW = BMS_PackVoltage * BMS_PackCurrent / 1000
+// WheelSpeedAvg = ma((WS_FL + WS_FR) / 2, 20)
+// Slip = (WS_RL - WS_FL) / max(WS_FL, 1)
+// ```
+// Expressions support + - * / ^, parentheses, and these functions:
+//  abs(x) sqrt(x) min(a, b) max(a, b)
+//  ma(x, n)        moving average over the last n samples
+//  lpf(x, tau_ms)  first order low pass with time constant tau_ms
+//  ddt(x)          derivative, per second
+//  integ(x)        trapezoidal integral, times seconds
+// Definitions can reference derived signals defined above them. Signals are referenced by their
+// column name, dots and all: `SME_TRQSPD.Speed * 2` with --naming message.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+
+enum Expr {
+    Num(f64),
+    Var(String),
+    Neg(Box<Expr>),
+    Bin(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>), // Stateless functions
+    Ma {
+        arg: Box<Expr>,
+        n: usize,
+        buf: VecDeque<f64>,
+        sum: f64,
+    },
+    Lpf {
+        arg: Box<Expr>,
+        tau_ms: f64,
+        last: Option<(f64, f64)>, // (time, filtered value)
+    },
+    Ddt {
+        arg: Box<Expr>,
+        last: Option<(f64, f64)>, // (time, input value)
+    },
+    Integ {
+        arg: Box<Expr>,
+        acc: f64,
+        last: Option<(f64, f64)>,
+    },
+}
+
+impl Expr {
+    /// Returns None until every input has been seen at least once
+    fn eval(&mut self, t: f64, values: &HashMap<String, f64>) -> Option<f64> {
+        match self {
+            Expr::Num(n) => Some(*n),
+            Expr::Var(name) => values.get(name).copied(),
+            Expr::Neg(a) => a.eval(t, values).map(|v| -v),
+            Expr::Bin(op, a, b) => {
+                let a = a.eval(t, values)?;
+                let b = b.eval(t, values)?;
+                Some(match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                })
+            }
+            Expr::Call(name, args) => {
+                let mut vals = Vec::with_capacity(args.len());
+                for a in args.iter_mut() {
+                    vals.push(a.eval(t, values)?);
+                }
+                Some(match name.as_str() {
+                    "abs" => vals[0].abs(),
+                    "sqrt" => vals[0].sqrt(),
+                    "min" => vals[0].min(vals[1]),
+                    _ => vals[0].max(vals[1]), // max, the parser doesn't let anything else through
+                })
+            }
+            Expr::Ma { arg, n, buf, sum } => {
+                let v = arg.eval(t, values)?;
+                buf.push_back(v);
+                *sum += v;
+                if buf.len() > *n {
+                    *sum -= buf.pop_front().unwrap();
+                }
+                Some(*sum / buf.len() as f64)
+            }
+            Expr::Lpf { arg, tau_ms, last } => {
+                let v = arg.eval(t, values)?;
+                let out = match *last {
+                    Some((lt, ly)) => {
+                        let alpha = (t - lt) / (*tau_ms + (t - lt));
+                        ly + alpha * (v - ly)
+                    }
+                    None => v,
+                };
+                *last = Some((t, out));
+                Some(out)
+            }
+            Expr::Ddt { arg, last } => {
+                let v = arg.eval(t, values)?;
+                let prev = last.replace((t, v));
+                match prev {
+                    Some((lt, lv)) if t > lt => Some((v - lv) / ((t - lt) / 1000.0)),
+                    _ => None,
+                }
+            }
+            Expr::Integ { arg, acc, last } => {
+                let v = arg.eval(t, values)?;
+                if let Some((lt, lv)) = last.replace((t, v)) {
+                    *acc += (v + lv) / 2.0 * ((t - lt) / 1000.0);
+                }
+                Some(*acc)
+            }
+        }
+    }
+
+    fn collect_vars(&self, out: &mut Vec<String>) {
+        match self {
+            Expr::Num(_) => {}
+            Expr::Var(name) => {
+                if !out.contains(name) {
+                    out.push(name.clone());
+                }
+            }
+            Expr::Neg(a) => a.collect_vars(out),
+            Expr::Bin(_, a, b) => {
+                a.collect_vars(out);
+                b.collect_vars(out);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|a| a.collect_vars(out)),
+            Expr::Ma { arg, .. }
+            | Expr::Lpf { arg, .. }
+            | Expr::Ddt { arg, .. }
+            | Expr::Integ { arg, .. } => arg.collect_vars(out),
+        }
+    }
+}
+
+pub struct DerivedSignal {
+    pub name: String,
+    expr: Expr,
+    dirty: bool,
+}
+
+impl DerivedSignal {
+    /// Every signal the expression reads
+    pub fn inputs(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        self.expr.collect_vars(&mut vars);
+        vars
+    }
+}
+
+pub struct DerivedEngine {
+    pub signals: Vec<DerivedSignal>,
+    values: HashMap<String, f64>, // Latest value of everything referenced by an expression
+    deps: HashMap<String, Vec<usize>>, // Input name -> derived signals that use it
+}
+
+impl DerivedEngine {
+    /// Loads definitions from a file, see the top of this file for the format
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut signals: Vec<DerivedSignal> = Vec::new();
+        let mut lines = Vec::new(); // Line number of each definition, for errors
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |e: String| format!("{}:{}: {}", path, lineno + 1, e);
+
+            let (name, expr) = line
+                .split_once('=')
+                .ok_or_else(|| err("expected `name = expression`".to_string()))?;
+            let name = name.trim().to_string();
+            if signals.iter().any(|s| s.name == name) {
+                return Err(err(format!("{} defined twice", name)));
+            }
+            let expr = Parser::new(expr).parse().map_err(err)?;
+            signals.push(DerivedSignal {
+                name,
+                expr,
+                dirty: false,
+            });
+            lines.push(lineno + 1);
+        }
+
+        // evaluate() goes top to bottom, so anything further down would still hold last frame's value
+        let mut deps: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, signal) in signals.iter().enumerate() {
+            for var in signal.inputs() {
+                if var == signal.name {
+                    return Err(format!(
+                        "{}:{}: {} uses itself",
+                        path, lines[idx], signal.name
+                    ));
+                }
+                if let Some(later) = signals[idx..].iter().find(|s| s.name == var) {
+                    return Err(format!(
+                        "{}:{}: {} uses {}, which has to be defined above it",
+                        path, lines[idx], signal.name, later.name
+                    ));
+                }
+                deps.entry(var).or_default().push(idx);
+            }
+        }
+
+        Ok(Self {
+            signals,
+            values: HashMap::new(),
+            deps,
+        })
+    }
+
+    /// Feed one decoded value. Cheap for signals no expression cares about.
+    pub fn update(&mut self, name: &str, value: f64) {
+        if let Some(users) = self.deps.get(name) {
+            for &idx in users {
+                self.signals[idx].dirty = true;
+            }
+            self.values.insert(name.to_string(), value);
+        }
+    }
+
+    /// Recompute every derived signal whose inputs changed since the last call.
+    /// Call once per frame, after all of the frame's signals went through update().
+    pub fn evaluate(&mut self, time_ms: f64) -> Vec<(usize, f64)> {
+        let mut out = Vec::new();
+        for idx in 0..self.signals.len() {
+            if !self.signals[idx].dirty {
+                continue;
+            }
+            self.signals[idx].dirty = false;
+            if let Some(value) = self.signals[idx].expr.eval(time_ms, &self.values) {
+                out.push((idx, value));
+                let name = self.signals[idx].name.clone();
+                self.update(&name, value); // Marks later derived signals that depend on this one
+            }
+        }
+        out
+    }
+}
+
+// ------- Expression parsing
+// expr    = term (('+' | '-') term)*
+// term    = power (('*' | '/') power)*
+// power   = unary ('^' power)?
+// unary   = '-' unary | primary
+// primary = number | ident | ident '(' expr (',' expr)* ')' | '(' expr ')'
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Sym(char),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Self {
+        let mut tokens = Vec::new();
+        let chars: Vec<char> = src.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() || c == '.' {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || chars[i] == '.'
+                        || chars[i] == 'e'
+                        || ((chars[i] == '-' || chars[i] == '+') && chars[i - 1] == 'e'))
+                {
+                    i += 1;
+                }
+                let s: String = chars[start..i].iter().collect();
+                // Bad numbers become a '?' token so the parser reports them
+                match s.parse() {
+                    Ok(n) => tokens.push(Token::Num(n)),
+                    Err(_) => tokens.push(Token::Sym('?')),
+                }
+            } else if c.is_alphanumeric() || c == '_' {
+                // Dots too, for `Message.Signal` column names (see --naming)
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            } else {
+                tokens.push(Token::Sym(c));
+                i += 1;
+            }
+        }
+        Self { tokens, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Expr, String> {
+        let e = self.expr()?;
+        match self.peek() {
+            None => Ok(e),
+            Some(t) => Err(format!("unexpected {:?}", t)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Sym(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", c))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.power()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.power()?));
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.unary()?;
+        if self.eat('^') {
+            // Right associative
+            return Ok(Expr::Bin('^', Box::new(base), Box::new(self.power()?)));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Sym('(')) => {
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Some(Token::Ident(name)) => {
+                if !self.eat('(') {
+                    return Ok(Expr::Var(name));
+                }
+                let mut args = vec![self.expr()?];
+                while self.eat(',') {
+                    args.push(self.expr()?);
+                }
+                self.expect(')')?;
+                make_call(name, args)
+            }
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn make_call(name: String, mut args: Vec<Expr>) -> Result<Expr, String> {
+    let arity = match name.as_str() {
+        "abs" | "sqrt" | "ddt" | "integ" => 1,
+        "min" | "max" | "ma" | "lpf" => 2,
+        _ => return Err(format!("unknown function {}", name)),
+    };
+    if args.len() != arity {
+        return Err(format!("{} takes {} argument(s)", name, arity));
+    }
+
+    // Filter parameters have to be plain numbers, they can't change while running
+    let constant = |e: &Expr| match e {
+        Expr::Num(n) => Ok(*n),
+        _ => Err(format!("second argument of {} must be a number", name)),
+    };
+
+    Ok(match name.as_str() {
+        "ma" => {
+            let n = constant(&args[1])?;
+            if n < 1.0 {
+                return Err("ma window must be at least 1".to_string());
+            }
+            Expr::Ma {
+                arg: Box::new(args.swap_remove(0)),
+                n: n as usize,
+                buf: VecDeque::new(),
+                sum: 0.0,
+            }
+        }
+        "lpf" => Expr::Lpf {
+            tau_ms: constant(&args[1])?,
+            arg: Box::new(args.swap_remove(0)),
+            last: None,
+        },
+        "ddt" => Expr::Ddt {
+            arg: Box::new(args.remove(0)),
+            last: None,
+        },
+        "integ" => Expr::Integ {
+            arg: Box::new(args.remove(0)),
+            acc: 0.0,
+            last: None,
+        },
+        _ => Expr::Call(name, args),
+    })
+}
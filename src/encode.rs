@@ -0,0 +1,247 @@
+// The other direction from data_loop: named physical values in, CAN frames out.
+// Used by `cantelope send` for bench testing, and by anything else that needs to make traffic.
+
+use dbc_rs::{ByteOrder, Dbc};
+use std::collections::HashMap;
+
+use crate::bits::signal_bits;
+use crate::dbcextra::{DbcExtras, MessageMux, raw_id};
+use crate::schema::DbcSource;
+
+/// Signals with these in their name get filled in automatically unless given a value
+const COUNTER_NAMES: [&str; 2] = ["counter", "alive"];
+const CRC_NAMES: [&str; 2] = ["crc", "checksum"];
+
+pub struct Encoder {
+    dbcs: Vec<Dbc>,
+    extras: DbcExtras,
+    counters: HashMap<(u32, String), u64>, // Alive counter state per message and signal
+}
+
+/// A frame ready for the bus
+pub struct Encoded {
+    pub id: u32, // Without the extended flag
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(sources: &[DbcSource]) -> Result<Self, String> {
+        let mut dbcs = Vec::new();
+        let mut extras = DbcExtras::default();
+        for (path, content) in sources {
+            dbcs.push(Dbc::parse(content).map_err(|e| format!("{}: {}", path, e))?);
+            extras.merge(DbcExtras::parse(content));
+        }
+        Ok(Self {
+            dbcs,
+            extras,
+            counters: HashMap::new(),
+        })
+    }
+
+    /// (name, id, signal names) of every message with data, in DBC order
+    pub fn messages(&self) -> Vec<(String, u32, Vec<String>)> {
+        let mut out = Vec::new();
+        for dbc in &self.dbcs {
+            for message in dbc.messages().iter() {
+                if message.dlc() == 0 {
+                    continue;
+                }
+                let signals = message
+                    .signals()
+                    .iter()
+                    .map(|s| s.name().to_string())
+                    .collect();
+                out.push((message.name().to_string(), raw_id(message.id()), signals));
+            }
+        }
+        out
+    }
+
+    /// GenMsgCycleTime, or its default, or 100 ms if the DBC doesn't say
+    pub fn cycle_time_ms(&self, id: u32) -> u32 {
+        self.extras
+            .cycle_times
+            .get(&raw_id(id))
+            .copied()
+            .or(self.extras.default_cycle_time)
+            .filter(|ms| *ms > 0) // 0 means event driven, nothing to go by
+            .unwrap_or(100)
+    }
+
+    /// `message` is a name, or an id in hex (0x1A0) or decimal.
+    /// Signals left out are zero, except alive counters (count up every call) and CRCs (CRC-8 SAE J1850
+    /// over the rest of the frame). Multiplexed signals are only placed when their switch selects them,
+    /// and the switch gets set from the first page's signal if it isn't given.
+    pub fn encode(
+        &mut self,
+        name_or_id: &str,
+        values: &HashMap<String, f64>,
+    ) -> Result<Encoded, String> {
+        let wanted_id = parse_message_id(name_or_id);
+        // Everything happens inside the loop, the messages only live as long as the iterator
+        for dbc in &self.dbcs {
+            for message in dbc.messages().iter() {
+                if message.name() != name_or_id && Some(raw_id(message.id())) != wanted_id {
+                    continue;
+                }
+                let id = raw_id(message.id());
+
+                let known: Vec<&str> = message.signals().iter().map(|s| s.name()).collect();
+                if let Some(unknown) = values.keys().find(|k| !known.contains(&k.as_str())) {
+                    return Err(format!("{} has no signal {}", message.name(), unknown));
+                }
+
+                let mut values = values.clone();
+                let mux = self.extras.mux_for(id);
+                if let Some(mux) = mux {
+                    fill_switches(mux, &mut values);
+                }
+
+                let mut data = vec![0u8; message.dlc() as usize];
+                let mut crc: Option<(Vec<usize>, bool)> = None;
+                for signal in message.signals().iter() {
+                    let name = signal.name();
+                    if let Some(mux) = mux
+                        && !mux.is_active(name, &|s: &str| values.get(s).copied())
+                    {
+                        continue;
+                    }
+
+                    let big_endian = matches!(signal.byte_order(), ByteOrder::BigEndian);
+                    let bits = signal_bits(
+                        signal.start_bit() as usize,
+                        signal.length() as usize,
+                        big_endian,
+                    );
+                    let lower = name.to_lowercase();
+
+                    let raw = match values.get(name) {
+                        Some(&value) if self.extras.is_float(id, name) => {
+                            float_bits(value, signal.factor(), signal.offset(), bits.len())
+                        }
+                        Some(&value) => raw_value(
+                            value,
+                            signal.factor(),
+                            signal.offset(),
+                            bits.len(),
+                            signal.is_unsigned(),
+                        ),
+                        None if CRC_NAMES.iter().any(|n| lower.contains(n)) => {
+                            crc = Some((bits, big_endian)); // Has to wait until everything else is in
+                            continue;
+                        }
+                        None if COUNTER_NAMES.iter().any(|n| lower.contains(n)) => {
+                            let counter = self.counters.entry((id, name.to_string())).or_default();
+                            let raw = *counter;
+                            *counter = (*counter + 1) & mask(bits.len());
+                            raw
+                        }
+                        None => 0,
+                    };
+                    put_bits(&mut data, &bits, raw, big_endian);
+                }
+
+                if let Some((bits, big_endian)) = crc {
+                    let covered: Vec<usize> = bits.iter().map(|b| b / 8).collect();
+                    let bytes: Vec<u8> = (0..data.len())
+                        .filter(|i| !covered.contains(i))
+                        .map(|i| data[i])
+                        .collect();
+                    put_bits(&mut data, &bits, u64::from(crc8_j1850(&bytes)), big_endian);
+                }
+
+                return Ok(Encoded {
+                    id,
+                    extended: message.id() & 0x8000_0000 != 0 || id > 0x7FF,
+                    data,
+                });
+            }
+        }
+        Err(format!("no message {} in the DBC", name_or_id))
+    }
+}
+
+/// If a page's signal is given but its switch isn't, pick the switch value that selects it
+fn fill_switches(mux: &MessageMux, values: &mut HashMap<String, f64>) {
+    let mut given: Vec<String> = values.keys().cloned().collect();
+    given.sort(); // HashMap order would make the pick random when two pages are given
+    for name in given {
+        if let Some(cond) = mux.conditions.get(&name)
+            && let Some(&(lo, _)) = cond.ranges.first()
+        {
+            values.entry(cond.switch.clone()).or_insert(lo as f64);
+        }
+    }
+}
+
+/// Physical value -> raw bits, clamped to what fits instead of wrapping around
+pub fn raw_value(value: f64, factor: f64, offset: f64, len: usize, unsigned: bool) -> u64 {
+    let raw = ((value - offset) / factor).round();
+    let len = len.min(64) as i32;
+    let (lo, hi) = if unsigned {
+        (0.0, 2f64.powi(len) - 1.0)
+    } else {
+        (-(2f64.powi(len - 1)), 2f64.powi(len - 1) - 1.0)
+    };
+    let raw = raw.clamp(lo, hi);
+    if unsigned {
+        raw as u64
+    } else {
+        (raw as i64 as u64) & mask(len as usize)
+    }
+}
+
+/// IEEE float signals (SIG_VALTYPE_) carry the float itself, the other way round from schema::FloatPlan
+pub fn float_bits(value: f64, factor: f64, offset: f64, len: usize) -> u64 {
+    let value = (value - offset) / factor;
+    if len == 32 {
+        u64::from((value as f32).to_bits())
+    } else {
+        value.to_bits()
+    }
+}
+
+fn mask(len: usize) -> u64 {
+    if len >= 64 { u64::MAX } else { (1 << len) - 1 }
+}
+
+/// Writes the value's bits into the positions signal_bits gave. Motorola positions start at the MSB.
+pub fn put_bits(data: &mut [u8], bits: &[usize], raw: u64, big_endian: bool) {
+    let len = bits.len();
+    for (i, &pos) in bits.iter().enumerate() {
+        let value_bit = if big_endian { len - 1 - i } else { i };
+        let Some(byte) = data.get_mut(pos / 8) else {
+            continue; // Past the DLC, check-dbc complains about these
+        };
+        if (raw >> value_bit) & 1 == 1 {
+            *byte |= 1 << (pos % 8);
+        } else {
+            *byte &= !(1 << (pos % 8));
+        }
+    }
+}
+
+/// CRC-8 SAE J1850: poly 0x1D, init 0xFF, final xor 0xFF. What AUTOSAR E2E profile 1 and most ECUs use.
+pub fn crc8_j1850(bytes: &[u8]) -> u8 {
+    let mut crc: u8 = 0xFF;
+    for &b in bytes {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc ^ 0xFF
+}
+
+fn parse_message_id(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
@@ -0,0 +1,76 @@
+// Plots to PNG or SVG without a window, for CI and the headless logger. Same panes and styling
+// as the plot window, see plot::draw_panes.
+//   `cantelope export run.parquet --plot a,b --xy lon,lat -o summary.png` draws a logged file
+//   `--export summary.svg` while logging draws what's in the plot buffers once the run is over
+
+use std::collections::{BTreeMap, HashMap};
+
+use plotters::prelude::*;
+
+use crate::args::Args;
+use crate::plot::{self, Pane};
+use crate::plotbuf::PlotStore;
+use crate::schema::SignalInfo;
+use crate::viewer::Session;
+use crate::workspace::Workspace;
+
+const WIDTH: u32 = 1600;
+const PANE_HEIGHT: u32 = 400;
+
+/// Draws the panes over `range` into a .svg, or a .png (or anything else the image crate knows)
+pub fn render(
+    path: &str,
+    store: &PlotStore,
+    info: &HashMap<String, SignalInfo>,
+    palette: &BTreeMap<String, RGBColor>,
+    panes: &[Pane],
+    range: (f64, f64),
+    size: Option<(u32, u32)>,
+) -> Result<(), String> {
+    if panes.is_empty() {
+        return Err("nothing to draw, use --plot, --xy or --workspace".to_string());
+    }
+    let size = size.unwrap_or((WIDTH, PANE_HEIGHT * panes.len() as u32));
+
+    if path.ends_with(".svg") {
+        let root = SVGBackend::new(path, size).into_drawing_area();
+        plot::draw_panes(&root, store, info, palette, panes, range);
+        root.present().map_err(|e| format!("{}: {}", path, e))
+    } else {
+        let root = BitMapBackend::new(path, size).into_drawing_area();
+        plot::draw_panes(&root, store, info, palette, panes, range);
+        root.present().map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// `cantelope export`
+pub fn run(args: &Args) {
+    if args.output.is_empty() {
+        panic!("export needs -o FILE.png or FILE.svg");
+    }
+    let mut session = Session::load(&args.input).unwrap_or_else(|e| panic!("{}", e));
+    if !args.dbcfiles.is_empty() {
+        session
+            .use_dbc(args)
+            .unwrap_or_else(|e| panic!("Failed to load DBC: {}", e));
+    }
+    let workspace = if args.workspace_file.is_empty() {
+        Workspace::default()
+    } else {
+        Workspace::load(&args.workspace_file)
+            .unwrap_or_else(|e| panic!("Failed to load workspace: {}", e))
+    };
+
+    let panes = plot::panes_from(&args.plots, &args.xy_plots, &workspace);
+    render(
+        &args.output,
+        &session.store,
+        &session.info,
+        &workspace.palette(),
+        &panes,
+        (session.start, session.end),
+        args.export_size,
+    )
+    .unwrap_or_else(|e| panic!("Export failed: {}", e));
+    println!("Wrote {}", args.output);
+}
@@ -1,42 +1,72 @@
-// DBC Parsing
-use dbc_rs::Dbc;
-use std::fs;
-
-// Arrow IP elements
-use arrow::datatypes::{DataType, Field, Schema};
 use std::sync::Arc;
 
-// Literally only for cleaner print
-use std::io::{self, Write};
-
 // Custom data storage helpers
 pub mod store;
-use store::{Column, GenericColumn};
+
+// DBC -> Arrow schema, and reloading it
+pub mod schema;
+
+// check-dbc mode
+pub mod bits;
+pub mod check;
+
+// Signal values -> frames, and the send subcommand
+pub mod encode;
+pub mod transmit;
 
 // Custom argument parsing
 pub mod args;
 
-// Custom Candump parsing
-use candump::CanDumpParser;
-
 // Custom TCP Can interface
 pub mod tcpwrapper;
 
-use crate::args::CanDataInput;
+// Input sources, and the receive/decode/storage threads around them
+pub mod pipeline;
+pub mod player;
+pub mod replay;
+pub mod simulate;
+pub mod source;
+use pipeline::StoreCmd;
+use source::FrameSource;
 
 // SocketCAN
 #[cfg(feature = "socket")]
 pub mod socketwrap;
 
-// Use ctrl+c as exit signal in stdin and socket mode
-use std::sync::atomic::{AtomicBool, Ordering};
+// Ctrl+c and the plot window's stop button both go through Stats::stop
+use std::sync::atomic::Ordering;
 
-// Allows data loop to send decoded values back to main thread
+// Plot data shared with the plot window, and the signal browser telling the data loop what it wants
 use std::sync::mpsc;
-use std::sync::mpsc::SyncSender;
+pub mod plotbuf;
+use plotbuf::PlotStore;
 
 pub mod plot;
-use plot::DataPoint;
+
+// Gauges, readouts, lamps and bar graphs above the plots
+pub mod dashboard;
+use dashboard::Dashboard;
+
+// Saved plot layouts
+pub mod workspace;
+use workspace::Workspace;
+
+// view mode, the plot window on a Parquet file
+pub mod viewer;
+
+// Plots to PNG/SVG without a window
+pub mod export;
+
+// Threshold alarms on decoded signals
+pub mod alarm;
+use alarm::{ActiveAlarms, AlarmEngine};
+
+// Virtual signals computed from decoded ones
+pub mod derived;
+use derived::DerivedEngine;
+
+// VAL_ tables and other things dbc_rs doesn't expose
+pub mod dbcextra;
 
 #[cfg(feature = "plot")]
 use plot::PlotWindow;
@@ -54,264 +84,384 @@ impl FloatExt for f64 {
 }
 
 fn main() {
-    let args = args::process_args(); // Load arguments into a struct
-
-    let dbc_content = fs::read_to_string(&args.dbcfile).unwrap(); // Load DBC file contents into string
+    let mut args = args::process_args(); // Load arguments into a struct
 
-    let (tx, rx) = mpsc::sync_channel::<DataPoint>(100); // For transfers from the data loop thread to main
+    if args.mode == args::Mode::CheckDbc {
+        let naming = schema::Naming::load(args.naming.clone(), &args.rename_file)
+            .unwrap_or_else(|e| panic!("Failed to load renames: {}", e));
+        if !check::run(&args.dbcfiles, &naming) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.mode == args::Mode::Play {
+        player::run(&args);
+        return;
+    }
+    if args.mode == args::Mode::Send {
+        transmit::run(&args);
+        return;
+    }
+    if args.mode == args::Mode::View {
+        viewer::run(&args);
+        return;
+    }
+    if args.mode == args::Mode::Export {
+        export::run(&args);
+        return;
+    }
 
-    let args_en_aux = args.en_aux;
+    // Load DBC file contents into strings
+    let dbc_sources = schema::read_sources(&args.dbcfiles).unwrap_or_else(|e| panic!("{}", e));
+
+    let plot_store: Arc<PlotStore> = Default::default(); // Decoded values for the plot window
+    let (plot_request_tx, plot_request_rx) = mpsc::channel::<Vec<String>>(); // And which signals the plot window wants
+
+    let active_alarms: ActiveAlarms = Default::default(); // Shared with the plot window for the alarm banner
+    let alarms = if args.alarmfile.is_empty() {
+        None
+    } else {
+        Some(
+            AlarmEngine::load(&args.alarmfile, active_alarms.clone())
+                .unwrap_or_else(|e| panic!("Failed to load alarms: {}", e)),
+        )
+    };
+
+    let derived = if args.derivedfile.is_empty() {
+        None
+    } else {
+        Some(
+            DerivedEngine::load(&args.derivedfile)
+                .unwrap_or_else(|e| panic!("Failed to load derived signals: {}", e)),
+        )
+    };
+
+    // Dashboard widgets need their signals sent over just like plotted ones
+    let dashboard = if args.dashboard_file.is_empty() {
+        None
+    } else {
+        let dashboard = Dashboard::load(&args.dashboard_file)
+            .unwrap_or_else(|e| panic!("Failed to load dashboard: {}", e));
+        args.aux_outputs.extend(dashboard.signals());
+        Some(dashboard)
+    };
+
+    // Same for the workspace's panes
+    let workspace = if args.workspace_file.is_empty() {
+        Workspace::default()
+    } else {
+        Workspace::load(&args.workspace_file)
+            .unwrap_or_else(|e| panic!("Failed to load workspace: {}", e))
+    };
+    args.aux_outputs.extend(workspace.signals());
+
+    let naming = schema::Naming::load(args.naming.clone(), &args.rename_file)
+        .unwrap_or_else(|e| panic!("Failed to load renames: {}", e));
+    let tables = schema::build(
+        &dbc_sources,
+        &naming,
+        derived.as_ref(),
+        &args.aux_outputs,
+        args.aggregate,
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    // Plot window only knows column names, so key units, ranges and value tables by those for it
+    let signal_info = tables.signal_info();
+    let catalog = tables.catalog();
+
+    let args_en_aux = args.en_aux && !args.no_gui; // --plot and friends still feed --export
     let args_plots = args.plots.clone(); // WHYYY
+    let args_xy_plots = args.xy_plots.clone();
+    let args_window_ms = args.window_ms;
+    let args_workspace_file = args.workspace_file.clone();
+    let args_export_size = args.export_size;
+
+    // --export draws the plotted signals once logging is done, with the window's panes
+    let export = (!args.export_file.is_empty()).then(|| {
+        (
+            args.export_file.clone(),
+            plot::panes_from(&args.plots, &args.xy_plots, &workspace),
+            workspace.palette(),
+            signal_info.clone(),
+        )
+    });
 
+    // Set up here rather than in the data thread, so the window can see it and stop it too
+    let stats = Arc::new(pipeline::Stats::default());
+    {
+        let stats = stats.clone();
+        ctrlc::set_handler(move || {
+            println!("\nShutdown signal received...");
+            stats.stop.store(true, Ordering::SeqCst);
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
+
+    // Enter in the terminal brings the plot window back after it's been closed. Not when stdin is the input.
+    if args_en_aux && !matches!(args.candatainput, args::CanDataInput::Stdin) {
+        let store = plot_store.clone();
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lines() {
+                store.request_reopen();
+            }
+        });
+    }
+
+    let store = plot_store.clone();
+    let data_stats = stats.clone();
     let handle = std::thread::spawn(move || {
-        data_loop(&args, &dbc_content, tx);
+        data_loop(
+            &args,
+            tables,
+            &naming,
+            store,
+            plot_request_rx,
+            alarms,
+            derived,
+            data_stats,
+        );
     });
 
     #[cfg(feature = "plot")]
     if args_en_aux {
-        _ = PlotWindow::run(rx, args_plots);
+        _ = PlotWindow::run(plot::Flags {
+            store: plot_store.clone(),
+            plots: args_plots,
+            xy: args_xy_plots,
+            alarms: active_alarms,
+            info: signal_info,
+            window_ms: args_window_ms,
+            catalog,
+            plot_requests: plot_request_tx,
+            dashboard,
+            workspace,
+            workspace_path: if args_workspace_file.is_empty() {
+                "workspace.toml".to_string() // Somewhere for "Save layout" to go
+            } else {
+                args_workspace_file
+            },
+            offline: false,
+            stats: Some(stats),
+        });
     }
 
     _ = handle.join();
-}
-
-fn data_loop(args: &args::Args, dbc_content: &String, tx: SyncSender<DataPoint>) {
-    let dbc = Dbc::parse(&dbc_content).unwrap(); // Parse DBC
-
-    // ------- CREATE SCHEMA
-    let mut base_row_size = 0; // Just to generate a cool "uncompressed data rate" number
-    let mut fields: Vec<Field> = Vec::new(); // vec of column descriptions, later dumped into Arc<Schema>
-    let mut columns: Vec<GenericColumn> = Vec::new(); // This vec actually stores the values
-    let mut is_filled: Vec<bool> = Vec::new(); // This will keep track of which values have been filled so ones which haven't can be null balanced
-
-    fields.push(Field::new(
-        "Time_ms",
-        DataType::Float64,
-        false, // Time is the only column that must exist in all rows.
-    ));
-
-    columns.push(GenericColumn::F64(Column::new())); // Column for time
 
-    is_filled.push(true); // This element of the map won't actually be used, but is needed for indecies to align
-
-    for message in dbc.messages().iter() {
-        for signal in message.signals().iter() {
-            is_filled.push(false); // If I ever update this to exclude ANY signals which are present in the DBC, I will need to move this into the blocks below
-            if signal.length() == 1
-                && signal.is_unsigned()
-                && signal.factor().is_nearly(1.0)
-                && signal.offset().is_nearly(0.0)
-            {
-                // Definetely a boolean
-                base_row_size += 1;
-                fields.push(Field::new(signal.name(), DataType::Boolean, true));
-                columns.push(GenericColumn::Bool(Column::new()));
-            } else if (signal.factor() % 1.0).is_nearly(1.0) {
-                // Definetely an integer
-                if signal.min() >= f64::from(i8::MIN) && signal.max() <= f64::from(i8::MAX) {
-                    // Fits in i8
-                    base_row_size += 8;
-                    fields.push(Field::new(signal.name(), DataType::Int8, true));
-                    columns.push(GenericColumn::I8(Column::new()));
-                } else if signal.min() >= f64::from(i16::MIN) && signal.max() <= f64::from(i16::MAX)
-                {
-                    // Fits in i16
-                    base_row_size += 16;
-                    fields.push(Field::new(signal.name(), DataType::Int16, true));
-                    columns.push(GenericColumn::I16(Column::new()));
-                } else if signal.min() >= f64::from(i32::MIN) && signal.max() <= f64::from(i32::MAX)
-                {
-                    // Fits in i32
-                    base_row_size += 32;
-                    fields.push(Field::new(signal.name(), DataType::Int32, true));
-                    columns.push(GenericColumn::I32(Column::new()));
-                } else {
-                    // must fits in i64 :shrug
-                    base_row_size += 64;
-                    fields.push(Field::new(signal.name(), DataType::Int64, true));
-                    columns.push(GenericColumn::I64(Column::new()));
-                }
-            } else {
-                // Float
-                //                if signal.min() >= f64::from(f16::MIN) && signal.max() <= f64::from(f16::MAX) {   // Fits in f16 (Currently only works in rust-unstable
-                //                    print!("f16");
-                //                    base_row_size+=16;
-                //                    fields.push(Field::new(signal.name(), DataType::Float16, true));
-                //                }
-
-                if signal.min() >= f64::from(f32::MIN) && signal.max() <= f64::from(f32::MAX) {
-                    base_row_size += 32;
-                    fields.push(Field::new(signal.name(), DataType::Float32, true));
-                    columns.push(GenericColumn::F32(Column::new()));
-                } else {
-                    // Must fits in f64 :shrug
-                    base_row_size += 64;
-                    fields.push(Field::new(signal.name(), DataType::Float64, true));
-                    columns.push(GenericColumn::F64(Column::new()));
-                }
-            }
+    if let Some((path, panes, palette, info)) = export {
+        let range = plot_store.span();
+        match export::render(
+            &path,
+            &plot_store,
+            &info,
+            &palette,
+            &panes,
+            range,
+            args_export_size,
+        ) {
+            Ok(()) => println!("Wrote {}", path),
+            Err(e) => println!("Export failed: {}", e),
         }
     }
-    println!("\nBasis row size: {} bits", base_row_size);
-    let schema = Arc::new(Schema::new(fields));
-    // ------
-
-    let mut parser = CanDumpParser::new(&String::default()).unwrap();
+}
 
-    #[cfg(feature = "socket")]
-    let mut cansocket: Option<socketwrap::CanWrapper> = None;
+#[allow(clippy::too_many_arguments)]
+fn data_loop(
+    args: &args::Args,
+    mut tables: schema::Tables,
+    naming: &schema::Naming,
+    plot_store: Arc<PlotStore>,
+    plot_requests: mpsc::Receiver<Vec<String>>,
+    mut alarms: Option<AlarmEngine>,
+    mut derived: Option<DerivedEngine>,
+    stats: Arc<pipeline::Stats>,
+) {
+    // Output goes to one file per DBC version, a reload starts the next one
+    let mut segment = 0;
+    let mut watcher = args
+        .watch_dbc
+        .then(|| schema::DbcWatcher::new(&args.dbcfiles));
+
+    let (source, time_start) = FrameSource::open(args, &stats.stop);
+
+    // Receive and storage get their own threads, this one just decodes
+    let (frame_tx, frame_rx) = crossbeam_channel::bounded(pipeline::FRAME_QUEUE);
+    let (store_tx, store_rx) = crossbeam_channel::bounded(pipeline::STORE_QUEUE);
+    let receiver = {
+        let stats = stats.clone();
+        std::thread::spawn(move || pipeline::receive(source, frame_tx, stats))
+    };
+    let reporter = {
+        let stats = stats.clone();
+        let (frames, writes) = (frame_rx.clone(), store_rx.clone());
+        std::thread::spawn(move || pipeline::report(stats, frames, writes))
+    };
+    let storage = args.en_ipm.then(|| {
+        let stats = stats.clone();
+        let output = args.output.clone();
+        std::thread::spawn(move || pipeline::store(store_rx, output, stats))
+    });
 
-    let mut tcpsocket: Option<tcpwrapper::TcpWrapper> = None;
+    let mut plotted = args.aux_outputs.clone(); // Survives DBC reloads
+    let mut plot_rings = plotbuf::rings_for(&tables, &plot_store);
+    let mut num_chunks = 0;
+    let mut relative_time_rcv = 0.0;
+    for frame in frame_rx.iter() {
+        let id = frame.id;
+        let data = frame.bytes();
+        relative_time_rcv = (frame.timestamp - time_start) * 1000.0; // time since start of recording
+        plot_store.set_latest(relative_time_rcv);
+        stats.decoded.fetch_add(1, Ordering::Relaxed);
+
+        // The signal browser changed what's plotted
+        while let Ok(list) = plot_requests.try_recv() {
+            tables.set_plotted(&list);
+            plot_rings = plotbuf::rings_for(&tables, &plot_store);
+            plotted = list;
+        }
 
-    let stdin = io::stdin();
-    let time_start;
+        // Swap in the new DBC before decoding this frame, so nothing gets lost across the change
+        if let Some(sources) = watcher.as_mut().and_then(|w| w.changed()) {
+            match schema::build(&sources, naming, derived.as_ref(), &plotted, args.aggregate) {
+                Ok(new_tables) => {
+                    if args.en_ipm {
+                        if tables.rows.row_started() {
+                            tables.rows.close_row(relative_time_rcv);
+                            stats.rows.fetch_add(1, Ordering::Relaxed);
+                            num_chunks += 1;
+                        }
+                        finish_segment(&mut tables, &store_tx);
 
-    match &args.candatainput {
-        CanDataInput::File => {
-            _ = parser = CanDumpParser::new(&args.input).unwrap();
-            parser.parse();
-            time_start = parser.get_timestamp();
-        }
-        CanDataInput::Stdin => {
-            let mut nextline = String::new();
-            stdin.read_line(&mut nextline).unwrap();
-            _ = parser.parse_string(nextline);
-            time_start = parser.get_timestamp();
-        }
-        #[cfg(feature = "socket")]
-        CanDataInput::Socket => {
-            cansocket = Some(socketwrap::CanWrapper::new(&args.input).unwrap());
-            _ = cansocket.as_mut().unwrap().parse();
-            time_start = cansocket.as_mut().unwrap().get_timestamp();
-        }
-        #[cfg(not(feature = "socket"))]
-        CanDataInput::Socket => {
-            panic!("Socketcan not enabled in this build")
-        }
-        CanDataInput::Remote => {
-            tcpsocket = Some(tcpwrapper::TcpWrapper::new(&args.input));
-            tcpsocket.as_mut().unwrap().parse().unwrap();
-            time_start = tcpsocket.as_mut().unwrap().get_timestamp();
+                        segment += 1;
+                        store_tx
+                            .send(StoreCmd::Open(store::segment_path(&args.output, segment)))
+                            .unwrap();
+                    }
+                    tables = new_tables;
+                    plot_rings = plotbuf::rings_for(&tables, &plot_store);
+                    println!(
+                        "\nReloaded {}, now writing to {}",
+                        args.dbcfiles.join(", "),
+                        store::segment_path(&args.output, segment)
+                    );
+                }
+                Err(e) => println!("\nDBC reload failed, keeping the old one: {}", e),
+            }
         }
-    }
 
-    let exit = Arc::new(AtomicBool::new(false));
-    let ex = exit.clone();
+        // Unknown ids go to the first DBC so its decode error gets printed
+        let plan = tables.plan.get(&dbcextra::raw_id(id));
+        let dbc = &tables.dbcs[plan.map(|p| p.dbc).unwrap_or(0)];
+        match dbc.decode(id, data, false) {
+            Ok(decoded) => {
+                // Multiplexed signals only count when their switch selects them, otherwise
+                // they're garbage decoded from some other page's bytes
+                let mux = tables.extras.mux_for(id);
+                let value_of =
+                    |name: &str| decoded.iter().find(|s| s.name == name).map(|s| s.value);
+
+                for (position, signal) in decoded.iter().enumerate() {
+                    if let Some(mux) = mux
+                        && !mux.is_active(signal.name, &value_of)
+                    {
+                        continue;
+                    }
 
-    ctrlc::set_handler(move || {
-        println!("\nShutdown signal received...");
-        ex.store(true, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
+                    // From here on the signal goes by its column name, see schema::Naming
+                    let Some(sp) = plan.and_then(|p| p.signal(position, signal.name)) else {
+                        continue;
+                    };
+                    let value = match &sp.float {
+                        Some(float) => float.decode(&data),
+                        None => signal.value,
+                    };
+
+                    // Alarms look at every value, not just the first one in each chunk
+                    if let Some(alarms) = alarms.as_mut() {
+                        alarms.update(&sp.name, relative_time_rcv, value);
+                    }
+                    if let Some(derived) = derived.as_mut() {
+                        derived.update(&sp.name, value);
+                    }
 
-    let mut num_chunks = 0;
-    while !exit.load(Ordering::SeqCst) {
-        // Message recieve loop
-        let timestamp;
-        let id;
-        let data;
-
-        match &args.candatainput {
-            CanDataInput::File => {
-                exit.store(parser.parse(), Ordering::SeqCst);
-
-                timestamp = parser.get_timestamp();
-                id = parser.get_id();
-                data = parser.get_data();
-            }
-            CanDataInput::Stdin => {
-                let mut nextline = String::new();
-                stdin.read_line(&mut nextline).unwrap();
-                exit.store(parser.parse_string(nextline), Ordering::SeqCst);
-
-                timestamp = parser.get_timestamp();
-                id = parser.get_id();
-                data = parser.get_data();
-            }
-            #[cfg(feature = "socket")]
-            CanDataInput::Socket => {
-                cansocket.as_mut().unwrap().parse().unwrap();
-                timestamp = cansocket.as_mut().unwrap().get_timestamp();
-                id = cansocket.as_mut().unwrap().get_id();
-                data = cansocket.as_mut().unwrap().get_data();
-            }
-            #[cfg(not(feature = "socket"))]
-            CanDataInput::Socket => {
-                panic!("Socketcan not yet supported")
-            }
-            CanDataInput::Remote => {
-                tcpsocket.as_mut().unwrap().parse().unwrap();
-                timestamp = tcpsocket.as_mut().unwrap().get_timestamp();
-                id = tcpsocket.as_mut().unwrap().get_id();
-                data = tcpsocket.as_mut().unwrap().get_data();
-            }
-        }
-        //if exit {continue;} // Prevents continued execution resulting in duplicated values when file is over, breaks finishing of last row
+                    // The plot gets every value, the column whatever --aggregate says
+                    if args.en_ipm {
+                        tables.rows.set(sp.col, sp.label_col, sp.agg, value);
+                    }
+                    if let Some(ring) = &plot_rings[sp.col] {
+                        ring.lock().unwrap().push(relative_time_rcv, value);
+                    }
+                }
 
-        let relative_time_rcv = (timestamp - time_start) * 1000.0; // time since start of recording
+                // Derived signals get computed once per frame, after all their inputs are in
+                if let Some(derived) = derived.as_mut() {
+                    for (idx, value) in derived.evaluate(relative_time_rcv) {
+                        let sp = &tables.derived_plan[idx];
+                        if let Some(alarms) = alarms.as_mut() {
+                            alarms.update(&sp.name, relative_time_rcv, value);
+                        }
 
-        match dbc.decode(id, &data, false) {
-            Ok(decoded) => {
-                for signal in decoded.iter() {
-                    let col = &mut columns[schema.index_of(signal.name).unwrap()];
-                    if !is_filled[schema.index_of(signal.name).unwrap()] {
-                        // Only save the first value from each chunk (as opposed to prev version saving last)
                         if args.en_ipm {
-                            match col {
-                                GenericColumn::Bool(c) => c.push(Some(signal.value.is_nearly(1.0))),
-                                GenericColumn::I8(c) => c.push(Some(signal.value as i8)),
-                                GenericColumn::I32(c) => c.push(Some(signal.value as i32)),
-                                GenericColumn::I64(c) => c.push(Some(signal.value as i64)),
-                                //                            GenericColumn::F16(c) => c.push(Some(f16::from(signal.value))),
-                                GenericColumn::F32(c) => c.push(Some(signal.value as f32)),
-                                GenericColumn::F64(c) => c.push(Some(signal.value)),
-                                _ => {}
-                            }
-                            is_filled[schema.index_of(signal.name).unwrap()] = true;
+                            tables.rows.set(sp.col, None, sp.agg, value);
                         }
-
-                        if args.aux_outputs.iter().any(|s| s == &signal.name) {
-                            let _ = tx.try_send((
-                                signal.name.to_string(),
-                                relative_time_rcv,
-                                signal.value,
-                            ));
+                        if let Some(ring) = &plot_rings[sp.col] {
+                            ring.lock().unwrap().push(relative_time_rcv, value);
                         }
                     }
                 }
             }
-            Err(e) => println!("Signal: {} Data: {:02x?}  Error: {}", id, &data, e),
-            //Err(e) => _ = e,
+            Err(e) => {
+                stats.decode_errors.fetch_add(1, Ordering::Relaxed);
+                println!("Signal: {} Data: {:02x?}  Error: {}", id, data, e)
+            } //Err(e) => _ = e,
         }
-        if relative_time_rcv > (&args.cache_ms * f64::from(num_chunks))
-            || exit.load(Ordering::SeqCst)
-        {
-            if args.en_ipm {
-                let col = &mut columns[schema.index_of("Time_ms").unwrap()];
-                is_filled[schema.index_of("Time_ms").unwrap()] = true;
-                match col {
-                    GenericColumn::F64(c) => c.push(Some(relative_time_rcv)),
-                    _ => {}
-                }
-                num_chunks += 1;
-
-                for (index, value) in is_filled.iter().enumerate() {
-                    if !value {
-                        columns[index].push_null();
-                    }
-                }
-
-                is_filled.fill(false);
+        if relative_time_rcv > (&args.cache_ms * f64::from(num_chunks)) && args.en_ipm {
+            tables.rows.close_row(relative_time_rcv);
+            stats.rows.fetch_add(1, Ordering::Relaxed);
+            num_chunks += 1;
+
+            // Events go out as they happen, so a crash or power cut doesn't lose them
+            if let Some(alarms) = alarms.as_mut()
+                && alarms.take_changed()
+                && !args.alarm_output.is_empty()
+            {
+                store::write_alarm_events_to_parquet(&alarms.events, &args.alarm_output).unwrap();
             }
-            if num_chunks % 250 == 0 {
-                print!("\rRow #{}", num_chunks);
-                io::stdout().flush().unwrap();
+
+            // Streaming mode writes a row group every stream_rows rows instead of holding it all in memory
+            if args.stream_rows > 0 && tables.rows.count() >= args.stream_rows {
+                store_tx.send(StoreCmd::Write(tables.take_batch())).unwrap();
             }
         }
     }
-    println!("");
+
+    // Source ran out (or ctrl-c), whatever is left becomes the last row
     if args.en_ipm {
-        let batch = store::finish_record_batch(columns, schema);
-        store::write_record_batch_to_parquet(&batch, &args.output).unwrap();
+        if tables.rows.row_started() {
+            tables.rows.close_row(relative_time_rcv);
+            stats.rows.fetch_add(1, Ordering::Relaxed);
+        }
+        finish_segment(&mut tables, &store_tx);
     }
+    drop(store_tx);
+    if let Some(storage) = storage {
+        _ = storage.join();
+    }
+    _ = receiver.join();
+    stats.done.store(true, Ordering::SeqCst);
+    _ = reporter.join();
+    println!("\n{}", stats.summary());
+
+    if let Some(alarms) = alarms {
+        println!("{} alarm events", alarms.events.len());
+        if !args.alarm_output.is_empty() {
+            store::write_alarm_events_to_parquet(&alarms.events, &args.alarm_output).unwrap();
+        }
+    }
+}
+
+/// Hands whatever rows haven't been written yet to the storage stage and finishes the file
+fn finish_segment(tables: &mut schema::Tables, store: &crossbeam_channel::Sender<StoreCmd>) {
+    store.send(StoreCmd::Write(tables.take_batch())).unwrap();
+    store.send(StoreCmd::Close).unwrap();
 }
@@ -0,0 +1,139 @@
+// The receive -> decode -> storage stages and the counters that show whether they keep up.
+// Receive and storage get their own threads, so a Parquet encode or a slow disk never holds up
+// the socket read. Decoding stays in data_loop.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use arrow::record_batch::RecordBatch;
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+
+use crate::source::{Frame, FrameSource};
+use crate::store::ParquetSink;
+
+pub const FRAME_QUEUE: usize = 8192; // About a second of a busy bus
+pub const STORE_QUEUE: usize = 16; // Batches waiting for the disk
+
+#[derive(Default)]
+pub struct Stats {
+    pub received: AtomicU64,
+    pub decoded: AtomicU64,
+    pub decode_errors: AtomicU64,
+    pub overruns: AtomicU64, // Frames dropped because the decode queue was full (live sources only)
+    pub backpressure: AtomicU64, // Times the reader had to wait for room in the decode queue
+    pub rows: AtomicU64,
+    pub batches: AtomicU64,    // Batches the storage stage has written
+    pub frame_rate: AtomicU64, // Frames/s over the last second, f64 bits
+    pub stop: AtomicBool, // Ctrl+c or the plot window's stop button, receive quits at the next frame or poll
+    pub done: AtomicBool, // Everything's written
+}
+
+impl Stats {
+    pub fn frame_rate(&self) -> f64 {
+        f64::from_bits(self.frame_rate.load(Ordering::Relaxed))
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} frames received, {} decoded, {} decode errors, {} overruns, {} backpressure waits, {} rows in {} batches",
+            self.received.load(Ordering::Relaxed),
+            self.decoded.load(Ordering::Relaxed),
+            self.decode_errors.load(Ordering::Relaxed),
+            self.overruns.load(Ordering::Relaxed),
+            self.backpressure.load(Ordering::Relaxed),
+            self.rows.load(Ordering::Relaxed),
+            self.batches.load(Ordering::Relaxed),
+        )
+    }
+}
+
+pub enum StoreCmd {
+    Open(String), // Following writes go to this file
+    Write(RecordBatch),
+    Close, // Finish the current file
+}
+
+/// Receive stage: reads frames as fast as the source gives them and queues them for decoding
+pub fn receive(mut source: FrameSource, tx: Sender<Frame>, stats: Arc<Stats>) {
+    let live = source.is_live();
+    while !stats.stop.load(Ordering::SeqCst) {
+        let Some(frame) = source.next(&stats.stop) else {
+            break;
+        };
+        stats.received.fetch_add(1, Ordering::Relaxed);
+
+        match tx.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(frame)) => {
+                if live {
+                    stats.overruns.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    stats.backpressure.fetch_add(1, Ordering::Relaxed);
+                    if tx.send(frame).is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => break,
+        }
+    }
+    // Dropping tx is what tells the decoder we're done
+}
+
+/// Storage stage: owns the Parquet file, the decoder just hands it batches
+pub fn store(rx: Receiver<StoreCmd>, mut path: String, stats: Arc<Stats>) {
+    let mut sink: Option<ParquetSink> = None;
+    for cmd in rx {
+        match cmd {
+            StoreCmd::Open(next) => path = next,
+            StoreCmd::Write(batch) => {
+                // The file gets created on the first batch, even an empty one, so every segment has its schema on disk
+                let fresh = sink.is_none();
+                let sink =
+                    sink.get_or_insert_with(|| ParquetSink::create(&path, batch.schema()).unwrap());
+                if fresh || batch.num_rows() > 0 {
+                    sink.write(&batch).unwrap();
+                }
+                stats.batches.fetch_add(1, Ordering::Relaxed);
+            }
+            StoreCmd::Close => {
+                if let Some(sink) = sink.take() {
+                    sink.close().unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Progress line, once a second. Queue depths near full mean a stage isn't keeping up.
+pub fn report(stats: Arc<Stats>, frames: Receiver<Frame>, writes: Receiver<StoreCmd>) {
+    let mut last = Instant::now();
+    let mut last_received = 0;
+    while !stats.done.load(Ordering::SeqCst) {
+        std::thread::sleep(Duration::from_millis(100));
+        if last.elapsed() < Duration::from_secs(1) {
+            continue;
+        }
+
+        let received = stats.received.load(Ordering::Relaxed);
+        let rate = (received - last_received) as f64 / last.elapsed().as_secs_f64();
+        last = Instant::now();
+        last_received = received;
+        stats.frame_rate.store(rate.to_bits(), Ordering::Relaxed);
+
+        print!(
+            "\rRow #{}  {:.0} frames/s  decode queue {}/{}  write queue {}/{}  overruns {}  backpressure {}   ",
+            stats.rows.load(Ordering::Relaxed),
+            rate,
+            frames.len(),
+            FRAME_QUEUE,
+            writes.len(),
+            STORE_QUEUE,
+            stats.overruns.load(Ordering::Relaxed),
+            stats.backpressure.load(Ordering::Relaxed),
+        );
+        io::stdout().flush().unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+// `cantelope play`: puts a logged run back onto real (or virtual) CAN interfaces with its
+// original timing, like canplayer. For hardware in the loop testing of ECUs, and for driving
+// the sender/relay chain end to end from a known log.
+
+#[derive(Default, Clone, Debug)]
+pub struct PlayOptions {
+    pub buses: Vec<(String, Option<String>)>, // (interface to send on, logged interface it takes), None takes everything
+    pub remap: Vec<(Option<String>, u32, u32)>, // (logged interface or any, from id, to id)
+    pub only: Vec<u32>,                       // Empty sends every id
+    pub skip: Vec<u32>,
+    pub delay_s: f64, // Wait this long before the first frame, the gaps after it stay as logged
+}
+
+impl PlayOptions {
+    /// Returns false if the argument isn't a play one
+    pub fn parse_arg(&mut self, arg: &str, rest: &mut impl Iterator<Item = String>) -> bool {
+        match arg {
+            // --bus vcan0=can0 sends what was logged on can0 out of vcan0, --bus vcan0 sends everything there
+            "--bus" => {
+                let value = rest.next().expect("--bus requires a value");
+                self.buses.push(match value.split_once('=') {
+                    Some((out, logged)) => (out.to_string(), Some(logged.to_string())),
+                    None => (value, None),
+                });
+            }
+            // --remap 100=200 or --remap can0:100=200, ids in hex like candump
+            "--remap" => {
+                let value = rest.next().expect("--remap requires a value");
+                let (bus, ids) = match value.split_once(':') {
+                    Some((bus, ids)) => (Some(bus.to_string()), ids),
+                    None => (None, value.as_str()),
+                };
+                let (from, to) = ids.split_once('=').expect("--remap expects FROM=TO");
+                self.remap.push((bus, parse_id(from), parse_id(to)));
+            }
+            "--only" => {
+                let value = rest.next().expect("--only requires a value");
+                self.only.extend(value.split(',').map(parse_id));
+            }
+            "--skip" => {
+                let value = rest.next().expect("--skip requires a value");
+                self.skip.extend(value.split(',').map(parse_id));
+            }
+            "--time-offset" => {
+                self.delay_s = rest
+                    .next()
+                    .expect("--time-offset requires a value")
+                    .parse()
+                    .unwrap();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(feature = "socket")]
+impl PlayOptions {
+    /// Which interface a frame logged on `interface` goes out on, None to drop it
+    fn route<'a>(&'a self, interface: &'a str) -> Option<&'a str> {
+        if self.buses.is_empty() {
+            return Some(interface); // Same name it was logged on
+        }
+        // Raw archives don't record the interface, so `logged` can't match. Fine with one --bus.
+        if interface.is_empty() {
+            return match self.buses.as_slice() {
+                [(out, _)] => Some(out.as_str()),
+                _ => panic!(
+                    "Raw archives don't record the interface, so they can only go out on one --bus"
+                ),
+            };
+        }
+        self.buses
+            .iter()
+            .find(|(_, logged)| logged.as_deref().is_none_or(|l| l == interface))
+            .map(|(out, _)| out.as_str())
+    }
+
+    fn wanted(&self, id: u32) -> bool {
+        (self.only.is_empty() || self.only.contains(&id)) && !self.skip.contains(&id)
+    }
+
+    fn remapped(&self, interface: &str, id: u32) -> u32 {
+        self.remap
+            .iter()
+            .find(|(bus, from, _)| *from == id && bus.as_deref().is_none_or(|b| b == interface))
+            .map(|(_, _, to)| *to)
+            .unwrap_or(id)
+    }
+}
+
+fn parse_id(s: &str) -> u32 {
+    let s = s.trim();
+    u32::from_str_radix(s.trim_start_matches("0x"), 16)
+        .unwrap_or_else(|_| panic!("{} isn't a hex CAN id", s))
+}
+
+#[cfg(feature = "socket")]
+pub fn run(args: &crate::args::Args) {
+    use crate::replay::Replay;
+    use crate::socketwrap::CanWrapper;
+    use std::collections::HashMap;
+
+    let options = &args.play;
+    let mut log = Replay::open(&args.input, args.replay.clone())
+        .unwrap_or_else(|e| panic!("{}: {}", args.input, e));
+    let mut sockets: HashMap<String, CanWrapper> = HashMap::new();
+
+    if options.delay_s > 0.0 {
+        std::thread::sleep(std::time::Duration::from_secs_f64(options.delay_s));
+    }
+
+    let mut sent = 0;
+    let mut errors = 0;
+    while let Some(frame) = log.next() {
+        if !options.wanted(frame.id) {
+            continue;
+        }
+        let Some(out) = options.route(&frame.interface) else {
+            continue;
+        };
+        if out.is_empty() {
+            panic!("Raw archives don't record the interface, pick one with --bus <interface>");
+        }
+        let socket = sockets.entry(out.to_string()).or_insert_with(|| {
+            CanWrapper::new(out).unwrap_or_else(|e| panic!("Can't open {}: {}", out, e))
+        });
+
+        let id = options.remapped(&frame.interface, frame.id);
+        match socket.send(id, frame.extended || id > 0x7FF, &frame.data) {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                errors += 1;
+                eprintln!("{} {:X}: {}", out, id, e);
+            }
+        }
+    }
+    println!("Sent {} frames, {} errors", sent, errors);
+}
+
+#[cfg(not(feature = "socket"))]
+pub fn run(_args: &crate::args::Args) {
+    panic!("Socketcan not enabled in this build")
+}
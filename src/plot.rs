@@ -1,38 +1,136 @@
-use iced::time;
-use iced::{Element, Length, Subscription, widget::Column};
+use iced::mouse::{self, Cursor, ScrollDelta};
+use iced::widget::{button, column, container, row, scrollable, text, text_input};
+use iced::{Color, Element, Length, Rectangle, Subscription, Task, event, widget::Column};
+use iced::{time, window};
+use plotters::coord::Shift;
 use plotters::prelude::*;
 use plotters_iced2::{Chart, ChartBuilder, ChartWidget};
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
-    sync::{Arc, Mutex, mpsc::Receiver},
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, atomic::Ordering, mpsc::Sender},
     time::{Duration, Instant},
 };
 
-pub type DataPoint = (String, f64, f64); // (signal, x, y)
+use crate::alarm::ActiveAlarms;
+use crate::dashboard::Dashboard;
+use crate::dbcextra::describe;
+use crate::pipeline::Stats;
+use crate::plotbuf::{PlotStore, decimate};
+use crate::schema::SignalInfo;
+use crate::workspace::Workspace;
 
-const X_WINDOW: f64 = 3000.0;
+pub type Catalog = Vec<(String, String, Vec<String>)>; // (node, message, signals), see Tables::catalog
+
+const DEFAULT_WINDOW_MS: f64 = 10000.0;
 const FPS_LIMIT: u64 = 25;
+const COARSE_BUCKETS: usize = 4096; // First decimation pass, done while holding the ring's lock
+
+// Where plotters puts the plotting area inside the widget, see build_chart. Needed to turn a
+// mouse position into a time.
+const MARGIN: f32 = 10.0;
+const Y_LABEL_AREA: f32 = 60.0;
+
+/// One chart and what's on it, also how it's saved in a workspace file
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pane {
+    pub traces: Vec<String>,
+    pub dbc_range: bool, // Y axes fixed to the DBC's [min|max] instead of following the data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<(f64, f64)>, // Fixed Y axis ranges, from the workspace file. Win over dbc_range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<(f64, f64)>,
+    // XY panes plot the traces against this signal instead of against time, see XyChart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>, // XY points colored by this signal's value, ex. speed on a GPS track
+    pub fade: bool, // Older XY points fade out
+    #[serde(skip)]
+    pub from_args: bool, // From --plot or --xy, which bring it back every run, so it isn't saved
+}
+
+impl Pane {
+    /// Traces, and the X and color signals of an XY pane
+    pub fn signals(&self) -> impl Iterator<Item = &String> {
+        self.traces.iter().chain(&self.x).chain(&self.color)
+    }
+}
+
+/// What clicking a signal in the browser makes it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Trace,
+    X,
+    Color,
+}
 
 pub struct PlotWindow {
-    receiver: Arc<Mutex<Receiver<DataPoint>>>,
-    signals: HashMap<String, VecDeque<(f64, f64)>>,
+    store: Arc<PlotStore>,
     last_redraw: Instant,
-    plots: Vec<Vec<String>>,
+    plots: Vec<Pane>,
+    alarms: ActiveAlarms,
+    info: HashMap<String, SignalInfo>, // Units, ranges and VAL_ descriptions by signal name
+    window_ms: f64,
+    latest_x: f64,                // Newest time seen, the right edge of the live view
+    paused_at: Option<f64>,       // Right edge frozen here while paused
+    resumed: u64,                 // Bumped on resume so every chart drops its zoom and pan
+    cursor: Option<(usize, f64)>, // Time under the mouse, and the pane it's over. Shown in every pane.
+    offline: bool,                // Viewing a file, see viewer.rs. Always paused.
+    catalog: Catalog,
+    plot_requests: Sender<Vec<String>>, // Tells the data thread which signals to send us
+    search: String,
+    show_browser: bool,
+    selected: usize, // Pane the browser adds traces to
+    role: Role,      // And what as
+    dashboard: Option<Dashboard>,
+    workspace_path: String,              // Where "Save layout" writes to
+    colors: BTreeMap<String, String>,    // From the workspace file, saved back as they were
+    saved_window_ms: f64, // The workspace file's too. window_ms can be --window-ms or a whole file.
+    palette: BTreeMap<String, RGBColor>, // The same, parsed
+    status: String,       // How the last save went
+    stats: Option<Arc<Stats>>, // The logger's, for the status bar and the stop button. None offline.
+    window: Option<window::Id>, // None while closed, logging goes on without it
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Tick,
+    TogglePause,
+    Pause,                      // A chart got zoomed or panned
+    Cursor(usize, Option<f64>), // Mouse moved over a pane, or left it
+    ToggleBrowser,
+    Search(String),
+    AddTrace(String),
+    RemoveTrace(usize, String),
+    NewPane,
+    DeletePane(usize),
+    SelectPane(usize),
+    ToggleDbcRange(usize),
+    ToggleFade(usize),
+    SetRole(Role),
+    SaveLayout,
+    StopLogging,
+    WindowClosed(window::Id),
 }
 
+/// Everything the window starts with, from main
+#[derive(Clone)]
 pub struct Flags {
-    pub receiver: Receiver<DataPoint>,
-}
-
-impl Default for Flags {
-    fn default() -> Self {
-        panic!("Flags::default() should never be used")
-    }
+    pub store: Arc<PlotStore>,
+    pub plots: Vec<Vec<String>>,
+    pub xy: Vec<Vec<String>>, // X, Y and maybe color, from --xy
+    pub alarms: ActiveAlarms,
+    pub info: HashMap<String, SignalInfo>,
+    pub window_ms: f64,
+    pub catalog: Catalog,
+    pub plot_requests: Sender<Vec<String>>,
+    pub dashboard: Option<Dashboard>,
+    pub workspace: Workspace,
+    pub workspace_path: String,
+    pub offline: bool,
+    pub stats: Option<Arc<Stats>>,
 }
 
 impl PlotWindow {
@@ -48,93 +146,538 @@ impl PlotWindow {
     //     })
     // }
 
-    pub fn run(receiver: Receiver<DataPoint>, _plots: Vec<Vec<String>>) -> iced::Result {
-        let receiver = Arc::new(Mutex::new(receiver));
-
-        iced::application(
-            {
-                let receiver = Arc::clone(&receiver);
-                move || PlotWindow {
-                    receiver: Arc::clone(&receiver),
-                    signals: HashMap::new(),
+    /// Runs until logging is over and the window is closed, whichever comes last. Closing the
+    /// window before that just closes it, `PlotStore::request_reopen` brings it back.
+    pub fn run(flags: Flags) -> iced::Result {
+        // --window-ms, then the workspace's, then the default
+        let window_ms = [flags.window_ms, flags.workspace.window_ms]
+            .into_iter()
+            .find(|&ms| ms > 0.0)
+            .unwrap_or(DEFAULT_WINDOW_MS);
+        let plots = panes_from(&flags.plots, &flags.xy, &flags.workspace);
+        // A daemon rather than an application, so it outlives its window
+        iced::daemon(
+            move || {
+                let mut window = PlotWindow {
+                    store: Arc::clone(&flags.store),
                     last_redraw: Instant::now(),
-                    plots: _plots.clone(), // ... Why? WHy? WHY? WHY DOES EVERYTHING NEED TO BE CLONE??? FUCK YOU RUST
-                }
+                    plots: plots.clone(),
+                    alarms: flags.alarms.clone(),
+                    info: flags.info.clone(),
+                    window_ms,
+                    latest_x: 0.0,
+                    // A file's session is over, so it's paused at the end of it for good
+                    paused_at: flags.offline.then(|| flags.store.latest()),
+                    resumed: 0,
+                    cursor: None,
+                    offline: flags.offline,
+                    catalog: flags.catalog.clone(),
+                    plot_requests: flags.plot_requests.clone(),
+                    search: String::new(),
+                    // Nothing to look at yet, so help pick something
+                    show_browser: plots.is_empty() && flags.dashboard.is_none(),
+                    selected: 0,
+                    role: Role::Trace,
+                    dashboard: flags.dashboard.clone(),
+                    workspace_path: flags.workspace_path.clone(),
+                    colors: flags.workspace.colors.clone(),
+                    saved_window_ms: flags.workspace.window_ms,
+                    palette: flags.workspace.palette(),
+                    status: String::new(),
+                    stats: flags.stats.clone(),
+                    window: None,
+                };
+                let open = window.open_window();
+                (window, open)
             },
             PlotWindow::update,
             PlotWindow::view,
         )
         .subscription(PlotWindow::subscription)
-        .title("Plots")
-        .centered()
+        .title(PlotWindow::title)
         .run()
     }
 
-    fn ingest_points(&mut self) {
-        if let Ok(receiver) = self.receiver.lock() {
-            while let Ok((name, x, y)) = receiver.try_recv() {
-                let series = self.signals.entry(name).or_default();
-
-                // 1. Add the new point
-                series.push_back((x, y));
+    fn title(&self, _window: window::Id) -> String {
+        "Plots".to_string()
+    }
 
-                // 2. The Delta-X Limit
-                // Get the newest X value we just pushed
-                let latest_x = x;
+    fn open_window(&mut self) -> Task<Message> {
+        let (id, open) = window::open(window::Settings {
+            position: window::Position::Centered,
+            ..Default::default()
+        });
+        self.window = Some(id);
+        open.discard()
+    }
 
-                // 3. Remove points from the front while they are outside the 3000-unit window
-                while let Some(&(oldest_x, _)) = series.front() {
-                    if latest_x - oldest_x > 10000.0 {
-                        series.pop_front();
-                    } else {
-                        // The oldest point is now within the window, so stop popping
-                        break;
-                    }
-                }
-            }
-        }
+    /// Everything's been written, or there was never anything to write
+    fn logging_done(&self) -> bool {
+        self.stats
+            .as_ref()
+            .is_none_or(|s| s.done.load(Ordering::SeqCst))
     }
 
-    fn update(&mut self, message: Message) {
+    fn update(&mut self, message: Message) -> Task<Message> {
         match message {
+            Message::Tick if self.window.is_none() => {
+                if self.logging_done() {
+                    return iced::exit(); // Nobody looking and nothing left to log
+                }
+                if self.store.take_reopen() {
+                    return self.open_window();
+                }
+            }
+            Message::WindowClosed(id) if self.window == Some(id) => {
+                self.window = None;
+                if self.logging_done() {
+                    return iced::exit();
+                }
+                println!(
+                    "\nPlot window closed, logging goes on. Enter opens it again, ctrl+c stops logging."
+                );
+            }
+            Message::WindowClosed(_) => {}
+            Message::StopLogging => {
+                if let Some(stats) = &self.stats {
+                    println!("\nStopped from the plot window...");
+                    stats.stop.store(true, Ordering::SeqCst);
+                }
+            }
             Message::Tick => {
                 // FPS cap
                 if self.last_redraw.elapsed() >= Duration::from_millis(1000 / FPS_LIMIT) {
-                    self.ingest_points();
+                    // Data keeps going into the store while paused, so nothing is missing after resume
+                    self.latest_x = self.store.latest();
                     self.last_redraw = Instant::now();
                 }
             }
+            Message::TogglePause if self.offline => self.resumed += 1, // Just resets the zoom
+            Message::TogglePause => {
+                if self.paused_at.take().is_some() {
+                    self.resumed += 1;
+                } else {
+                    self.paused_at = Some(self.latest_x);
+                }
+            }
+            Message::Cursor(pane, Some(x)) => self.cursor = Some((pane, x)),
+            Message::Cursor(pane, None) => {
+                // Only the pane the mouse just left gets to clear it
+                if self.cursor.is_some_and(|(p, _)| p == pane) {
+                    self.cursor = None;
+                }
+            }
+            Message::Pause => {
+                if self.paused_at.is_none() {
+                    self.paused_at = Some(self.latest_x);
+                }
+            }
+            Message::ToggleBrowser => self.show_browser = !self.show_browser,
+            Message::Search(search) => self.search = search,
+            Message::AddTrace(name) => {
+                if self.plots.is_empty() {
+                    self.plots.push(Pane::default());
+                    self.selected = 0;
+                }
+                let pane = &mut self.plots[self.selected];
+                match self.role {
+                    Role::Trace if !pane.traces.contains(&name) => pane.traces.push(name),
+                    Role::Trace => {}
+                    Role::X => pane.x = Some(name), // Makes it an XY pane
+                    Role::Color => pane.color = Some(name),
+                }
+                self.send_plotted();
+            }
+            Message::RemoveTrace(pane, name) => {
+                if let Some(pane) = self.plots.get_mut(pane) {
+                    pane.traces.retain(|n| *n != name);
+                    // Taking away the X goes back to plotting against time
+                    if pane.x.as_ref() == Some(&name) {
+                        pane.x = None;
+                    }
+                    if pane.color.as_ref() == Some(&name) {
+                        pane.color = None;
+                    }
+                    self.send_plotted();
+                }
+            }
+            Message::NewPane => {
+                self.plots.push(Pane::default());
+                self.selected = self.plots.len() - 1;
+            }
+            Message::DeletePane(pane) => {
+                if pane < self.plots.len() {
+                    self.plots.remove(pane);
+                    self.cursor = None;
+                    self.selected = self.selected.min(self.plots.len().saturating_sub(1));
+                    self.send_plotted();
+                }
+            }
+            Message::SelectPane(pane) => self.selected = pane,
+            Message::ToggleDbcRange(pane) => {
+                if let Some(pane) = self.plots.get_mut(pane) {
+                    pane.dbc_range = !pane.dbc_range;
+                }
+            }
+            Message::ToggleFade(pane) => {
+                if let Some(pane) = self.plots.get_mut(pane) {
+                    pane.fade = !pane.fade;
+                }
+            }
+            Message::SetRole(role) => self.role = role,
+            Message::SaveLayout => {
+                let workspace = Workspace {
+                    window_ms: self.saved_window_ms,
+                    colors: self.colors.clone(),
+                    panes: self
+                        .plots
+                        .iter()
+                        .filter(|p| !p.from_args)
+                        .cloned()
+                        .collect(),
+                };
+                self.status = match workspace.save(&self.workspace_path) {
+                    Ok(()) => format!("Saved to {}", self.workspace_path),
+                    Err(e) => format!("Save failed: {}", e),
+                };
+            }
         }
+        Task::none()
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        time::every(std::time::Duration::from_millis(40)).map(|_| Message::Tick) // 25 FPS
+        Subscription::batch([
+            time::every(std::time::Duration::from_millis(40)).map(|_| Message::Tick), // 25 FPS
+            window::close_events().map(Message::WindowClosed),
+        ])
     }
 
-    fn view(&self) -> Element<'_, Message> {
-        // 1. Map over the outer Vec to create a list of widgets
-        let charts: Vec<Element<Message>> = self
+    fn view(&self, _window: window::Id) -> Element<'_, Message> {
+        // 1. Map over the outer Vec to create a list of widgets, each with a header to manage it
+        let mut charts: Vec<Element<Message>> = self
             .plots
             .iter()
-            .map(|plot_group| {
-                ChartWidget::new(SignalChart {
-                    signals: &self.signals,
-                    // Pass the inner Vec<String> to the toplot field
-                    toplot: plot_group.clone(),
-                })
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .into() // Convert each ChartWidget into a generic Element
+            .enumerate()
+            .map(|(idx, pane)| {
+                let title = if idx == self.selected && self.show_browser {
+                    format!("> Pane {}", idx + 1)
+                } else {
+                    format!("Pane {}", idx + 1)
+                };
+                let mut header =
+                    row![button(text(title).size(13)).on_press(Message::SelectPane(idx))]
+                        .spacing(5)
+                        .align_y(iced::Alignment::Center);
+                let roles = pane.traces.iter().map(|n| ("", n));
+                let roles = roles
+                    .chain(pane.x.iter().map(|n| ("X: ", n)))
+                    .chain(pane.color.iter().map(|n| ("Color: ", n)));
+                for (role, name) in roles {
+                    header = header.push(
+                        button(text(format!("{}{} x", role, name)).size(12))
+                            .style(button::secondary)
+                            .on_press(Message::RemoveTrace(idx, name.clone())),
+                    );
+                }
+                if pane.x.is_some() {
+                    header = header.push(
+                        button(text("Fade").size(12))
+                            .style(if pane.fade {
+                                button::primary
+                            } else {
+                                button::secondary
+                            })
+                            .on_press(Message::ToggleFade(idx)),
+                    );
+                }
+                header = header.push(
+                    button(text("DBC range").size(12))
+                        .style(if pane.dbc_range {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .on_press(Message::ToggleDbcRange(idx)),
+                );
+                header = header.push(
+                    button(text("Delete pane").size(12))
+                        .style(button::danger)
+                        .on_press(Message::DeletePane(idx)),
+                );
+
+                let chart: Element<Message> = match &pane.x {
+                    Some(x) => ChartWidget::new(XyChart {
+                        store: &self.store,
+                        info: &self.info,
+                        palette: &self.palette,
+                        pane,
+                        x,
+                        range: self.live_range(),
+                        cursor: self.cursor.map(|(_, x)| x),
+                    })
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+                    None => ChartWidget::new(SignalChart {
+                        store: &self.store,
+                        info: &self.info,
+                        palette: &self.palette,
+                        pane,
+                        live_range: self.live_range(),
+                        resumed: self.resumed,
+                        index: idx,
+                        cursor: self.cursor.map(|(_, x)| x),
+                    })
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .into(),
+                };
+
+                column![header, chart].height(Length::Fill).into()
             })
             .collect();
 
+        let mut controls = row![
+            button(text(if self.offline {
+                "Reset zoom"
+            } else if self.paused_at.is_some() {
+                "Resume"
+            } else {
+                "Pause"
+            }))
+            .on_press(Message::TogglePause),
+            button(text("Signals")).on_press(Message::ToggleBrowser),
+            button(text("New pane")).on_press(Message::NewPane),
+            button(text("Save layout")).on_press(Message::SaveLayout),
+            text(if self.status.is_empty() {
+                "Wheel zooms, drag pans (both pause), hover for values"
+            } else {
+                self.status.as_str()
+            })
+            .size(14),
+        ]
+        .spacing(10)
+        .padding(5)
+        .align_y(iced::Alignment::Center);
+        if let Some(stats) = &self.stats
+            && !stats.stop.load(Ordering::SeqCst)
+        {
+            controls = controls.push(
+                button(text("Stop logging"))
+                    .style(button::danger)
+                    .on_press(Message::StopLogging),
+            );
+        }
+        if let Some(dashboard) = &self.dashboard {
+            // A file's dashboard shows what things were at the cursor, a live one the latest values
+            let at = self.cursor.filter(|_| self.offline).map(|(_, x)| x);
+            charts.insert(0, dashboard.view(&self.store, &self.info, at));
+        }
+        charts.insert(0, controls.into());
+
+        // Alarm banner goes on top when anything is active
+        let active = self.alarms.lock().map(|a| a.clone()).unwrap_or_default();
+        if !active.is_empty() {
+            let banner = container(
+                text(format!("ALARM: {}", active.join("  |  ")))
+                    .size(22)
+                    .color(Color::WHITE),
+            )
+            .padding(8)
+            .width(Length::Fill)
+            .style(|_| container::background(Color::from_rgb(0.8, 0.1, 0.1)));
+            charts.insert(0, banner.into());
+        }
+
+        if let Some(stats) = &self.stats {
+            charts.push(status_bar(stats));
+        }
+
         // 2. Place all charts into a Column for a vertical layout
         let content = Column::with_children(charts)
             .spacing(10) // Optional: adds a gap between your charts
             .width(Length::Fill)
             .height(Length::Fill);
 
-        content.into()
+        if self.show_browser {
+            row![self.browser(), content].into()
+        } else {
+            content.into()
+        }
+    }
+
+    /// Sidebar listing every signal by node and message. Clicking one adds it to the selected pane,
+    /// clicking it again takes it out.
+    fn browser(&self) -> Element<'_, Message> {
+        let search = self.search.to_lowercase();
+        let in_pane = self.plots.get(self.selected);
+
+        let mut list = Column::new().spacing(2);
+        let mut last_node = None;
+        for (node, message, signals) in &self.catalog {
+            // Searching for a message shows all of it, otherwise just the signals that match
+            let whole = message.to_lowercase().contains(&search);
+            let matching: Vec<&String> = signals
+                .iter()
+                .filter(|s| whole || s.to_lowercase().contains(&search))
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+
+            if last_node != Some(node) {
+                let node_name = if node.is_empty() { "(no sender)" } else { node };
+                list = list.push(text(node_name).size(16));
+                last_node = Some(node);
+            }
+            list = list.push(text(format!("  {}", message)).size(13));
+            for signal in matching {
+                let plotted = in_pane.is_some_and(|p| p.signals().any(|s| s == signal));
+                let entry = if plotted {
+                    button(text(format!("* {}", signal)).size(13))
+                        .style(button::primary)
+                        .on_press(Message::RemoveTrace(self.selected, signal.clone()))
+                } else {
+                    button(text(signal.as_str()).size(13))
+                        .style(button::text)
+                        .on_press(Message::AddTrace(signal.clone()))
+                };
+                list = list.push(entry.padding([1, 12]));
+            }
+        }
+
+        // Clicking a signal adds it as a trace, or as an XY pane's X or color
+        let role = |label, role| {
+            button(text(label).size(13))
+                .style(if self.role == role {
+                    button::primary
+                } else {
+                    button::secondary
+                })
+                .on_press(Message::SetRole(role))
+        };
+        let roles = row![
+            text("Add as").size(13),
+            role("Y", Role::Trace),
+            role("X", Role::X),
+            role("Color", Role::Color),
+        ]
+        .spacing(5)
+        .align_y(iced::Alignment::Center);
+
+        column![
+            roles,
+            text_input("Search signals", &self.search).on_input(Message::Search),
+            scrollable(list).height(Length::Fill),
+        ]
+        .spacing(5)
+        .padding(5)
+        .width(300)
+        .into()
+    }
+
+    /// Every signal in any pane or on the dashboard, for the data thread
+    fn send_plotted(&self) {
+        let mut plotted: Vec<String> = self
+            .plots
+            .iter()
+            .flat_map(|p| p.signals().cloned())
+            .chain(self.dashboard.iter().flat_map(|d| d.signals()))
+            .collect();
+        plotted.sort();
+        plotted.dedup();
+        _ = self.plot_requests.send(plotted); // Only fails once logging is over
+    }
+}
+
+/// What the logger is up to, along the bottom of the window
+fn status_bar(stats: &Stats) -> Element<'_, Message> {
+    let state = if stats.done.load(Ordering::SeqCst) {
+        "Logging done, output written"
+    } else if stats.stop.load(Ordering::SeqCst) {
+        "Stopping..."
+    } else {
+        "Logging"
+    };
+    text(format!(
+        "{}   |   {} rows   |   {:.0} frames/s   |   {} decode errors   |   {} overruns",
+        state,
+        stats.rows.load(Ordering::Relaxed),
+        stats.frame_rate(),
+        stats.decode_errors.load(Ordering::Relaxed),
+        stats.overruns.load(Ordering::Relaxed),
+    ))
+    .size(13)
+    .into()
+}
+
+/// --plot panes first, then --xy, the workspace's after them
+pub fn panes_from(plots: &[Vec<String>], xy: &[Vec<String>], workspace: &Workspace) -> Vec<Pane> {
+    let xy = xy.iter().map(|signals| Pane {
+        traces: signals.get(1).cloned().into_iter().collect(),
+        x: signals.first().cloned(),
+        color: signals.get(2).cloned(),
+        fade: true,
+        from_args: true,
+        ..Default::default()
+    });
+    plots
+        .iter()
+        .map(|traces| Pane {
+            traces: traces.clone(), // ... Why? WHy? WHY? WHY DOES EVERYTHING NEED TO BE CLONE??? FUCK YOU RUST
+            from_args: true,
+            ..Default::default()
+        })
+        .chain(xy)
+        .chain(workspace.panes.iter().cloned())
+        .collect()
+}
+
+/// Panes one above the other, drawn the same as in the window, onto any plotters backend.
+/// For `export`, which has no window.
+pub fn draw_panes<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    store: &PlotStore,
+    info: &HashMap<String, SignalInfo>,
+    palette: &BTreeMap<String, RGBColor>,
+    panes: &[Pane],
+    range: (f64, f64),
+) {
+    root.fill(&WHITE).unwrap();
+    let areas = root.split_evenly((panes.len(), 1));
+    for (index, (pane, area)) in panes.iter().zip(&areas).enumerate() {
+        let builder = ChartBuilder::on(area);
+        match &pane.x {
+            Some(x) => XyChart {
+                store,
+                info,
+                palette,
+                pane,
+                x,
+                range,
+                cursor: None,
+            }
+            .build_chart(&(), builder),
+            None => SignalChart {
+                store,
+                info,
+                palette,
+                pane,
+                live_range: range,
+                resumed: 0,
+                index,
+                cursor: None,
+            }
+            .build_chart(&ChartState::default(), builder),
+        }
+    }
+}
+
+impl PlotWindow {
+    /// The last window_ms up to now, or up to where it was paused
+    fn live_range(&self) -> (f64, f64) {
+        let right = self.paused_at.unwrap_or(self.latest_x).max(self.window_ms);
+        (right - self.window_ms, right)
     }
 }
 
@@ -149,79 +692,519 @@ impl PlotWindow {
 // }
 
 struct SignalChart<'a> {
-    signals: &'a HashMap<String, VecDeque<(f64, f64)>>,
-    toplot: Vec<String>,
+    store: &'a PlotStore,
+    info: &'a HashMap<String, SignalInfo>,
+    palette: &'a BTreeMap<String, RGBColor>,
+    pane: &'a Pane,
+    live_range: (f64, f64),
+    resumed: u64,
+    index: usize,        // Which pane this is, for Message::Cursor
+    cursor: Option<f64>, // Shared by all panes
 }
 
-impl<'a> Chart<Message> for SignalChart<'a> {
-    type State = ();
+/// Name, points on screen, and the value the legend shows
+type Trace<'a> = (&'a String, Vec<(f64, f64)>, Option<f64>);
 
-    fn build_chart<DB: DrawingBackend>(&self, state: &Self::State, mut builder: ChartBuilder<DB>) {
-        _ = state;
-
-        let mut min_x = f64::INFINITY;
-        let mut max_x = f64::NEG_INFINITY;
-        let mut max_y = -100000.0;
-        let mut min_y = 100000.0;
-
-        for name in &self.toplot {
-            if let Some(series) = self.signals.get(name) {
-                for &(_x, y) in series {
-                    if max_y < y {
-                        max_y = y;
-                    }
-                    if min_y > y {
-                        min_y = y;
-                    }
-                }
+/// Per pane, kept by iced between frames
+#[derive(Default)]
+struct ChartState {
+    view: Option<(f64, f64)>, // Zoomed or panned X range, None follows live_range
+    hovered: bool,            // Mouse is over this pane
+    drag: Option<(f32, (f64, f64))>, // Where a drag started, and the range at that point
+    resumed: u64,             // Last resume this pane has seen
+}
+
+impl<'a> SignalChart<'a> {
+    fn x_range(&self, state: &ChartState) -> (f64, f64) {
+        match state.view {
+            Some(view) if state.resumed == self.resumed => view,
+            _ => self.live_range,
+        }
+    }
+
+    fn unit(&self, name: &str) -> &str {
+        self.info.get(name).map(|i| i.unit.as_str()).unwrap_or("")
+    }
+
+    /// The first trace's unit gets the left axis, everything in other units goes on the right
+    fn on_right(&self, name: &str) -> bool {
+        match self.pane.traces.first() {
+            Some(first) => self.unit(name) != self.unit(first),
+            None => false,
+        }
+    }
+
+    fn right_axis(&self) -> f32 {
+        if self.pane.traces.iter().any(|t| self.on_right(t)) {
+            Y_LABEL_AREA
+        } else {
+            0.0
+        }
+    }
+
+    /// The workspace's range for the axis, or the DBC's [min|max] of the axis' traces if the
+    /// pane wants that and the DBC has one, otherwise whatever is on screen with a bit of room
+    fn y_range(&self, traces: &[Trace], fixed: Option<(f64, f64)>) -> (f64, f64) {
+        if let Some(range) = fixed {
+            return range;
+        }
+        if self.pane.dbc_range
+            && let Some(range) = dbc_range(self.info, traces.iter().map(|(name, _, _)| *name))
+        {
+            return range;
+        }
+        padded_range(
+            traces
+                .iter()
+                .flat_map(|(_, points, _)| points.iter().map(|&(_, y)| y)),
+        )
+    }
+}
+
+/// Union of the signals' DBC [min|max], if any of them have one
+fn dbc_range<'a>(
+    info: &HashMap<String, SignalInfo>,
+    names: impl Iterator<Item = &'a String>,
+) -> Option<(f64, f64)> {
+    names
+        .filter_map(|name| info.get(name).and_then(|i| i.range))
+        .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+}
+
+/// What the values span, with a bit of room
+fn padded_range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    let (lo, hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), y| {
+        (lo.min(y), hi.max(y))
+    });
+    if !lo.is_finite() {
+        return (0.0, 1.0); // Nothing on screen yet
+    }
+    let pad = if hi > lo { (hi - lo) * 0.05 } else { 1.0 };
+    (lo - pad, hi + pad)
+}
+
+/// Same signal, same color, every run and in every pane. Unless the workspace says otherwise, or
+/// another trace in the pane already has that color, then it moves on to the next free one.
+fn trace_color(name: &str, traces: &[String], palette: &BTreeMap<String, RGBColor>) -> RGBColor {
+    if let Some(color) = palette.get(name) {
+        return *color;
+    }
+    let pick = |index: usize| {
+        let (r, g, b) = Palette99::pick(index).rgb();
+        RGBColor(r, g, b)
+    };
+    // Workspace colors are taken first, then the traces above this one in the pane
+    let mut taken: Vec<RGBColor> = traces
+        .iter()
+        .filter_map(|t| palette.get(t).copied())
+        .collect();
+    for trace in traces {
+        if palette.contains_key(trace) {
+            continue;
+        }
+        let mut index = palette_index(trace);
+        // Bounded in case a pane ever has more traces than there are colors
+        for _ in 0..Palette99::COLORS.len() {
+            if !taken.contains(&pick(index)) {
+                break;
             }
+            index += 1;
+        }
+        if trace == name {
+            return pick(index);
         }
+        taken.push(pick(index));
+    }
+    pick(palette_index(name)) // Not in the pane
+}
+
+/// FNV-1a of the name, since std's hasher is seeded randomly
+fn palette_index(name: &str) -> usize {
+    let hash = name.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+    (hash % Palette99::COLORS.len() as u64) as usize
+}
 
-        for series in self.signals.values() {
-            for &(x, _y) in series {
-                if min_x > x {
-                    min_x = x;
+/// Fraction of the way across the plotting area, for a position relative to the widget
+fn plot_fraction(x: f32, bounds: Rectangle, right_axis: f32) -> f32 {
+    let width = (bounds.width - MARGIN * 2.0 - Y_LABEL_AREA - right_axis).max(1.0);
+    ((x - MARGIN - Y_LABEL_AREA) / width).clamp(0.0, 1.0)
+}
+impl<'a> Chart<Message> for SignalChart<'a> {
+    type State = ChartState;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: &iced::Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let iced::Event::Mouse(event) = event else {
+            return (event::Status::Ignored, None);
+        };
+        // A resume since the last event throws away this pane's zoom and pan
+        if state.resumed != self.resumed {
+            state.resumed = self.resumed;
+            state.view = None;
+            state.drag = None;
+        }
+
+        let Some(position) = cursor.position_in(bounds) else {
+            state.drag = None;
+            let left = std::mem::take(&mut state.hovered);
+            return (
+                event::Status::Ignored,
+                left.then_some(Message::Cursor(self.index, None)),
+            );
+        };
+        state.hovered = true;
+        let fraction = plot_fraction(position.x, bounds, self.right_axis());
+        let (from, to) = self.x_range(state);
+        let at = from + (to - from) * f64::from(fraction);
+
+        match event {
+            mouse::Event::WheelScrolled { delta } => {
+                let lines = match delta {
+                    ScrollDelta::Lines { y, .. } => *y,
+                    ScrollDelta::Pixels { y, .. } => *y / 40.0,
+                };
+                // Zoom around the cursor, so what's under it stays put
+                let scale = 0.8f64.powf(f64::from(lines));
+                state.view = Some((at - (at - from) * scale, at + (to - at) * scale));
+                (event::Status::Captured, Some(Message::Pause))
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                state.drag = Some((fraction, (from, to)));
+                (event::Status::Captured, None)
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                state.drag = None;
+                (event::Status::Captured, None)
+            }
+            mouse::Event::CursorMoved { .. } => match state.drag {
+                Some((start, (from, to))) => {
+                    let shift = (to - from) * f64::from(start - fraction);
+                    state.view = Some((from + shift, to + shift));
+                    (event::Status::Captured, Some(Message::Pause))
                 }
+                None => (
+                    event::Status::Captured,
+                    Some(Message::Cursor(self.index, Some(at))),
+                ),
+            },
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        state: &Self::State,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> mouse::Interaction {
+        if state.drag.is_some() {
+            mouse::Interaction::Grabbing
+        } else if cursor.is_over(bounds) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn build_chart<DB: DrawingBackend>(&self, state: &Self::State, mut builder: ChartBuilder<DB>) {
+        let (min_x, max_x) = self.x_range(state);
+        // Another pane's cursor can be off this one's screen
+        let cursor_x = self.cursor.filter(|x| (min_x..=max_x).contains(x));
 
-                if max_x < x {
-                    max_x = x;
+        // Copy out what's on screen, roughly decimated, so the rings aren't locked while drawing.
+        // Along with the value the legend shows: under the cursor, or the latest one.
+        let traces: Vec<Trace> = self
+            .pane
+            .traces
+            .iter()
+            .map(|name| match self.store.get(name) {
+                Some(ring) => {
+                    let ring = ring.lock().unwrap();
+                    let points = decimate(ring.visible(min_x, max_x), min_x, max_x, COARSE_BUCKETS);
+                    let value = match cursor_x {
+                        Some(x) => ring.value_at(x),
+                        None => ring.last().map(|(_, y)| y),
+                    };
+                    (name, points, value)
                 }
+                None => (name, Vec::new(), None),
+            })
+            .collect();
+
+        let (right, left): (Vec<Trace>, Vec<Trace>) = traces
+            .into_iter()
+            .partition(|(name, _, _)| self.on_right(name));
+        let (left_lo, left_hi) = self.y_range(&left, self.pane.left);
+        let (right_lo, right_hi) = self.y_range(&right, self.pane.right);
+
+        let mut chart = builder
+            .margin(MARGIN as u32)
+            .x_label_area_size(40)
+            .y_label_area_size(Y_LABEL_AREA as u32)
+            .right_y_label_area_size(self.right_axis() as u32)
+            .build_cartesian_2d(min_x..max_x, left_lo..left_hi)
+            .unwrap()
+            .set_secondary_coord(min_x..max_x, right_lo..right_hi);
+
+        // A pane with just one enum signal gets its VAL_ descriptions on the Y axis instead of numbers
+        let enum_table = match self.pane.traces.as_slice() {
+            [only] => self.info.get(only).and_then(|i| i.values.as_ref()),
+            _ => None,
+        };
+        let enum_label = |y: &f64| {
+            if (y - y.round()).abs() > 1e-6 {
+                return String::new();
             }
+            enum_table
+                .and_then(|t| describe(t, *y))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let mut mesh = chart.configure_mesh();
+        if enum_table.is_some() {
+            mesh.y_label_formatter(&enum_label);
+        }
+        if let Some((name, _, _)) = left.first() {
+            mesh.y_desc(self.unit(name));
         }
+        mesh.draw().unwrap();
 
-        if !min_x.is_finite() {
-            min_x = 0.0;
-            max_x = X_WINDOW;
+        if !right.is_empty() {
+            let mut units: Vec<&str> = right.iter().map(|(name, _, _)| self.unit(name)).collect();
+            units.dedup();
+            chart
+                .configure_secondary_axes()
+                .y_desc(units.join(", "))
+                .draw()
+                .unwrap();
         }
 
-        let chart = builder
-            .margin(10)
-            .x_label_area_size(40)
-            .y_label_area_size(60);
+        // Down to a min and max per pixel column
+        let pixels = chart.plotting_area().dim_in_pixel().0 as usize;
+
+        let sides = left.into_iter().map(|t| (t, false));
+        for ((name, points, value), secondary) in sides.chain(right.into_iter().map(|t| (t, true)))
+        {
+            // Create a style with a specific stroke width (e.g., 3 pixels)
+            let style = trace_color(name, &self.pane.traces, self.palette).stroke_width(3);
+
+            // Enums get their description in the legend, everything else its unit
+            let label = match (value, self.info.get(name)) {
+                (
+                    Some(y),
+                    Some(SignalInfo {
+                        values: Some(table),
+                        ..
+                    }),
+                ) => {
+                    format!("{} = {}", name, describe(table, y).unwrap_or("?"))
+                }
+                (Some(y), _) if cursor_x.is_some() => {
+                    format!("{} = {:.3} {}", name, y, self.unit(name))
+                }
+                _ => name.clone(),
+            };
+
+            let series = LineSeries::new(decimate(points.into_iter(), min_x, max_x, pixels), style);
+            let anno = if secondary {
+                chart.draw_secondary_series(series)
+            } else {
+                chart.draw_series(series)
+            };
+            anno.unwrap()
+                .label(label)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], style));
+        }
+
+        // Cursor line, with the time it's at
+        if let Some(x) = cursor_x {
+            chart
+                .draw_series(std::iter::once(PathElement::new(
+                    vec![(x, left_lo), (x, left_hi)],
+                    BLACK.mix(0.5),
+                )))
+                .unwrap();
+            chart
+                .draw_series(std::iter::once(Text::new(
+                    format!("{:.1} s", x / 1000.0),
+                    (x, left_hi),
+                    ("sans-serif", 14),
+                )))
+                .unwrap();
+        }
 
-        let mut chart = chart
-            .build_cartesian_2d(min_x..max_x, min_y - 0.01..max_y + 0.01)
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft) // This moves it to the top left
+            .background_style(&TRANSPARENT)
+            .border_style(&TRANSPARENT)
+            .draw()
             .unwrap();
+    }
+}
+
+/// Most points an XY trace draws, more than that gets thinned out evenly
+const XY_POINTS: usize = 20000;
+
+/// Traces against the pane's X signal instead of against time. Shows the same time range the
+/// other panes follow (the whole file when viewing one), with the shared cursor marked on it.
+struct XyChart<'a> {
+    store: &'a PlotStore,
+    info: &'a HashMap<String, SignalInfo>,
+    palette: &'a BTreeMap<String, RGBColor>,
+    pane: &'a Pane,
+    x: &'a String,
+    range: (f64, f64),
+    cursor: Option<f64>,
+}
+
+/// (x, y, color signal's value, time)
+type XyPoint = (f64, f64, Option<f64>, f64);
 
-        chart.configure_mesh().draw().unwrap();
+impl XyChart<'_> {
+    /// Every value of the trace in range, paired with whatever X and the color signal last were
+    /// when it came in
+    fn points(&self, name: &str) -> Vec<XyPoint> {
+        let (from, to) = self.range;
+        let Some(ring) = self.store.get(name) else {
+            return Vec::new();
+        };
+        let ys: Vec<(f64, f64)> = ring
+            .lock()
+            .unwrap()
+            .visible(from, to)
+            .filter(|(t, _)| (from..=to).contains(t))
+            .collect();
+        let ys: Vec<(f64, f64)> = ys
+            .into_iter()
+            .step_by((ys.len() / XY_POINTS).max(1))
+            .collect();
+
+        // One lock at a time, X or color can be the same signal as the trace
+        let at = |signal: Option<&String>| -> Vec<Option<f64>> {
+            match signal.and_then(|s| self.store.get(s)) {
+                Some(ring) => {
+                    let ring = ring.lock().unwrap();
+                    ys.iter().map(|&(t, _)| ring.value_at(t)).collect()
+                }
+                None => vec![None; ys.len()],
+            }
+        };
+        let xs = at(Some(self.x));
+        let colors = at(self.pane.color.as_ref());
+
+        ys.iter()
+            .zip(xs)
+            .zip(colors)
+            .filter_map(|((&(t, y), x), c)| Some((x?, y, c, t)))
+            .collect()
+    }
 
-        for (idx, (name, series)) in self.signals.iter().enumerate() {
-            if self.toplot.contains(name) {
-                let color = Palette99::pick(idx);
-                // Create a style with a specific stroke width (e.g., 3 pixels)
-                let style = color.stroke_width(3);
+    /// X and Y at the cursor's time
+    fn at_cursor(&self, name: &str) -> Option<(f64, f64)> {
+        let t = self.cursor?;
+        let value = |signal: &str| self.store.get(signal)?.lock().unwrap().value_at(t);
+        Some((value(self.x)?, value(name)?))
+    }
+}
+
+/// Blue for low, red for high
+fn heat(fraction: f64) -> HSLColor {
+    HSLColor(0.66 * (1.0 - fraction.clamp(0.0, 1.0)), 0.9, 0.5)
+}
+
+impl Chart<Message> for XyChart<'_> {
+    type State = ();
 
+    fn build_chart<DB: DrawingBackend>(&self, _state: &Self::State, mut builder: ChartBuilder<DB>) {
+        let traces: Vec<(&String, Vec<XyPoint>)> = self
+            .pane
+            .traces
+            .iter()
+            .map(|name| (name, self.points(name)))
+            .collect();
+        let all = || traces.iter().flat_map(|(_, points)| points.iter());
+
+        let x_dbc = dbc_range(self.info, std::iter::once(self.x)).filter(|_| self.pane.dbc_range);
+        let (x_lo, x_hi) = x_dbc.unwrap_or_else(|| padded_range(all().map(|p| p.0)));
+        let y_dbc = dbc_range(self.info, self.pane.traces.iter()).filter(|_| self.pane.dbc_range);
+        let (y_lo, y_hi) = self
+            .pane
+            .left
+            .or(y_dbc)
+            .unwrap_or_else(|| padded_range(all().map(|p| p.1)));
+        // The color signal's DBC range if it has one, so colors mean the same thing all session
+        let color = self.pane.color.as_ref();
+        let (c_lo, c_hi) = dbc_range(self.info, color.into_iter())
+            .unwrap_or_else(|| padded_range(all().filter_map(|p| p.2)));
+
+        let unit = |name: &str| self.info.get(name).map(|i| i.unit.as_str()).unwrap_or("");
+        if let Some(c) = color {
+            builder.caption(
+                format!("Color: {} {:.1} to {:.1} {}", c, c_lo, c_hi, unit(c)),
+                ("sans-serif", 14),
+            );
+        }
+        let mut chart = builder
+            .margin(MARGIN as u32)
+            .x_label_area_size(40)
+            .y_label_area_size(Y_LABEL_AREA as u32)
+            .build_cartesian_2d(x_lo..x_hi, y_lo..y_hi)
+            .unwrap();
+        let mut mesh = chart.configure_mesh();
+        mesh.x_desc(format!("{} {}", self.x, unit(self.x)));
+        if let Some(first) = self.pane.traces.first() {
+            mesh.y_desc(unit(first));
+        }
+        mesh.draw().unwrap();
+
+        let (from, to) = self.range;
+        for (name, points) in &traces {
+            let base = trace_color(name, &self.pane.traces, self.palette);
+            let style = move |c: Option<f64>, t: f64| {
+                // Newest points solid, the oldest ones in range barely there
+                let alpha = if self.pane.fade && to > from {
+                    0.1 + 0.9 * ((t - from) / (to - from)).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                match c {
+                    Some(c) if c_hi > c_lo => heat((c - c_lo) / (c_hi - c_lo)).mix(alpha).filled(),
+                    _ => base.mix(alpha).filled(),
+                }
+            };
+            chart
+                .draw_series(
+                    points
+                        .iter()
+                        .map(|&(x, y, c, t)| Circle::new((x, y), 2, style(c, t))),
+                )
+                .unwrap()
+                .label(format!("{} vs {}", name, self.x))
+                .legend(move |(x, y)| Circle::new((x + 10, y), 4, base.filled()));
+
+            if let Some(point) = self.at_cursor(name) {
                 chart
-                    .draw_series(LineSeries::new(series.iter().copied(), style))
-                    .unwrap()
-                    .label(name)
-                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], style));
+                    .draw_series(std::iter::once(Circle::new(
+                        point,
+                        7,
+                        BLACK.stroke_width(2),
+                    )))
+                    .unwrap();
             }
         }
 
         chart
             .configure_series_labels()
-            .position(SeriesLabelPosition::UpperLeft) // This moves it to the top left
+            .position(SeriesLabelPosition::UpperLeft)
             .background_style(&TRANSPARENT)
             .border_style(&TRANSPARENT)
             .draw()
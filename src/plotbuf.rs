@@ -0,0 +1,185 @@
+// Plot data shared between the decode thread and the plot window. Every decoded value of a
+// plotted signal goes into that signal's ring, nothing gets dropped when the GUI is slow.
+// The GUI reads what's on screen and decimates it to the pane's pixel width with min/max,
+// so spikes still show at any zoom.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::schema::Tables;
+
+/// Points kept per signal, about 8 MB each. At 1 kHz that's over 8 minutes.
+const RING_POINTS: usize = 1 << 19;
+
+pub type SharedRing = Arc<Mutex<Ring>>;
+
+#[derive(Default)]
+pub struct Ring {
+    points: VecDeque<(f64, f64)>, // (time ms, value), oldest first
+}
+
+impl Ring {
+    /// A whole session at once, for the Parquet viewer. Isn't held to RING_POINTS.
+    pub fn from_points(points: Vec<(f64, f64)>) -> Self {
+        Self {
+            points: points.into(),
+        }
+    }
+
+    pub fn push(&mut self, x: f64, y: f64) {
+        if self.points.len() >= RING_POINTS {
+            self.points.pop_front();
+        }
+        self.points.push_back((x, y));
+    }
+
+    /// Points from `from` to `to`, plus one either side so lines run off the edges
+    pub fn visible(&self, from: f64, to: f64) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let start = self.points.partition_point(|&(x, _)| x < from);
+        let end = self.points.partition_point(|&(x, _)| x <= to);
+        self.points
+            .range(start.saturating_sub(1)..(end + 1).min(self.points.len()))
+            .copied()
+    }
+
+    /// Last value at or before x
+    pub fn value_at(&self, x: f64) -> Option<f64> {
+        let idx = self.points.partition_point(|&(px, _)| px <= x);
+        idx.checked_sub(1).map(|i| self.points[i].1)
+    }
+
+    pub fn first(&self) -> Option<(f64, f64)> {
+        self.points.front().copied()
+    }
+
+    pub fn last(&self) -> Option<(f64, f64)> {
+        self.points.back().copied()
+    }
+}
+
+#[derive(Default)]
+pub struct PlotStore {
+    rings: Mutex<HashMap<String, SharedRing>>,
+    latest: AtomicU64,  // Newest time pushed, f64 bits
+    reopen: AtomicBool, // The window was closed while logging and someone asked for it back
+}
+
+impl PlotStore {
+    /// The signal's ring, made if it doesn't have one yet
+    pub fn ring(&self, name: &str) -> SharedRing {
+        self.rings
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .clone()
+    }
+
+    pub fn insert(&self, name: &str, ring: Ring) {
+        self.rings
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Arc::new(Mutex::new(ring)));
+    }
+
+    pub fn get(&self, name: &str) -> Option<SharedRing> {
+        self.rings.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn set_latest(&self, x: f64) {
+        self.latest.store(x.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn latest(&self) -> f64 {
+        f64::from_bits(self.latest.load(Ordering::Relaxed))
+    }
+
+    pub fn request_reopen(&self) {
+        self.reopen.store(true, Ordering::Relaxed);
+    }
+
+    /// True once per request
+    pub fn take_reopen(&self) -> bool {
+        self.reopen.swap(false, Ordering::Relaxed)
+    }
+
+    /// Oldest point still in any ring, to the newest time pushed
+    pub fn span(&self) -> (f64, f64) {
+        let first = self
+            .rings
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|ring| ring.lock().unwrap().first())
+            .map(|(x, _)| x)
+            .fold(f64::INFINITY, f64::min);
+        (first.min(self.latest()), self.latest())
+    }
+}
+
+/// Rings for the plotted columns, indexed by column so the decode loop doesn't hash names.
+/// Has to be redone whenever the plan changes (DBC reload, signal browser).
+pub fn rings_for(tables: &Tables, store: &PlotStore) -> Vec<Option<SharedRing>> {
+    let mut rings = vec![None; tables.schema.fields().len()];
+    let signals = tables.plan.values().flat_map(|m| m.signals.iter());
+    for signal in signals.chain(tables.derived_plan.iter()) {
+        if signal.plot {
+            rings[signal.col] = Some(store.ring(&signal.name));
+        }
+    }
+    rings
+}
+
+/// Min and max of each of `buckets` equal slices of from..to, in time order. Draws the same
+/// envelope as every point would, with a couple of points per pixel.
+pub fn decimate(
+    points: impl Iterator<Item = (f64, f64)>,
+    from: f64,
+    to: f64,
+    buckets: usize,
+) -> Vec<(f64, f64)> {
+    let width = (to - from) / buckets.max(1) as f64;
+    let mut out = Vec::with_capacity(buckets * 2 + 2);
+    let mut current: Option<(i64, (f64, f64), (f64, f64))> = None; // (bucket, min, max)
+
+    let flush = |out: &mut Vec<(f64, f64)>, (_, min, max): (i64, (f64, f64), (f64, f64))| {
+        if min == max {
+            out.push(min);
+        } else if min.0 <= max.0 {
+            out.extend([min, max]);
+        } else {
+            out.extend([max, min]);
+        }
+    };
+
+    for point in points {
+        // Points off either edge get a bucket of their own so the line still leaves the screen
+        let bucket = if width > 0.0 {
+            ((point.0 - from) / width)
+                .floor()
+                .clamp(-1.0, buckets as f64) as i64
+        } else {
+            0
+        };
+        if let Some((b, min, max)) = &mut current
+            && *b == bucket
+        {
+            if point.1 < min.1 {
+                *min = point;
+            }
+            if point.1 > max.1 {
+                *max = point;
+            }
+            continue;
+        }
+        if let Some(done) = current.take() {
+            flush(&mut out, done);
+        }
+        current = Some((bucket, point, point));
+    }
+    if let Some(done) = current {
+        flush(&mut out, done);
+    }
+    out
+}
@@ -0,0 +1,233 @@
+// Plays a recorded log back with its original timing, so the live path (plots, alarms, the
+// sender's TCP stream) can be exercised without the car.
+// Reads candump -L text or raw frame archives (the relay's 24 byte frames back to back,
+// e.g. `nc car 9000 > run.bin`). Kept free of the rest of the crate so `sender` can use it too.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct ReplayOptions {
+    pub speed: f64,         // 1 is real time, 2 twice as fast, 0 as fast as possible
+    pub looped: bool,       // Start over at the end, timestamps keep counting up
+    pub start_s: f64,       // Skip this far into the log
+    pub end_s: Option<f64>, // Stop (or loop) this far into the log
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            looped: false,
+            start_s: 0.0,
+            end_s: None,
+        }
+    }
+}
+
+impl ReplayOptions {
+    /// Shared by cantelope and sender. Returns false if the argument isn't a replay one.
+    pub fn parse_arg(&mut self, arg: &str, rest: &mut impl Iterator<Item = String>) -> bool {
+        match arg {
+            "--speed" => {
+                let value = rest.next().expect("--speed requires a value");
+                self.speed = match value.as_str() {
+                    "max" => 0.0,
+                    v => v.trim_end_matches('x').parse().unwrap(),
+                };
+            }
+            "--loop" => self.looped = true,
+            "--start" => {
+                self.start_s = rest
+                    .next()
+                    .expect("--start requires a value")
+                    .parse()
+                    .unwrap();
+            }
+            "--end" => {
+                self.end_s = Some(
+                    rest.next()
+                        .expect("--end requires a value")
+                        .parse()
+                        .unwrap(),
+                );
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoggedFrame {
+    pub timestamp: f64,
+    pub interface: String, // Empty for raw archives, they don't record it
+    pub id: u32,           // Without the extended flag, same as socketwrap hands out
+    pub extended: bool,
+    pub data: Vec<u8>,
+}
+
+/// One `(1759876075.171400) can0 288#8A2C642B00000000` line. Remote frames and junk give None.
+pub fn parse_candump_line(line: &str) -> Option<LoggedFrame> {
+    let mut tokens = line.split_whitespace();
+    let timestamp = tokens
+        .next()?
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .parse()
+        .ok()?;
+    let interface = tokens.next()?.to_string();
+    let (id, data) = tokens.next()?.split_once('#')?;
+    if data.starts_with('R') {
+        return None;
+    }
+    // CAN FD frames are ID##<flags digit><data>
+    let data = match data.strip_prefix('#') {
+        Some(fd) => fd.get(1..)?,
+        None => data,
+    };
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(LoggedFrame {
+        timestamp,
+        interface,
+        id: u32::from_str_radix(id, 16).ok()? & 0x1FFF_FFFF,
+        extended: id.len() > 3,
+        data: bytes,
+    })
+}
+
+enum LogReader {
+    Candump(BufReader<File>),
+    Raw(BufReader<File>),
+}
+
+impl LogReader {
+    /// Raw archives are sniffed by their first byte, candump lines always start with '('
+    fn open(path: &str) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let raw = reader.fill_buf()?.first().is_some_and(|b| *b != b'(');
+        Ok(if raw {
+            LogReader::Raw(reader)
+        } else {
+            LogReader::Candump(reader)
+        })
+    }
+
+    fn next(&mut self) -> Option<LoggedFrame> {
+        match self {
+            LogReader::Candump(reader) => {
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).ok()? == 0 {
+                        return None;
+                    }
+                    if let Some(frame) = parse_candump_line(&line) {
+                        return Some(frame);
+                    }
+                }
+            }
+            LogReader::Raw(reader) => {
+                // timestamp f64, id u32, padding u32, data [u8; 8], in the sender's byte order
+                let mut buffer = [0u8; 24];
+                reader.read_exact(&mut buffer).ok()?;
+                Some(LoggedFrame {
+                    timestamp: f64::from_ne_bytes(buffer[0..8].try_into().unwrap()),
+                    interface: String::new(),
+                    id: u32::from_ne_bytes(buffer[8..12].try_into().unwrap()),
+                    extended: false,
+                    data: buffer[16..24].to_vec(),
+                })
+            }
+        }
+    }
+}
+
+pub struct Replay {
+    path: String,
+    options: ReplayOptions,
+    reader: LogReader,
+    log_start: Option<f64>, // Timestamp of the first frame in the log
+    loop_offset: f64,       // Added to timestamps so each loop carries on where the last one ended
+    last_timestamp: f64,
+    played: bool, // Anything played since the last restart, so a window with no frames can't spin forever
+    wall_start: Option<(Instant, f64)>, // When playback started, and the log time it started at
+}
+
+impl Replay {
+    pub fn open(path: &str, options: ReplayOptions) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.to_string(),
+            reader: LogReader::open(path)?,
+            options,
+            log_start: None,
+            loop_offset: 0.0,
+            last_timestamp: 0.0,
+            played: false,
+            wall_start: None,
+        })
+    }
+
+    /// Blocks until it's time for the next frame. None once the log (or --end) is reached and not looping.
+    pub fn next(&mut self) -> Option<LoggedFrame> {
+        let mut frame = loop {
+            let Some(frame) = self.reader.next() else {
+                if self.restart() {
+                    continue;
+                }
+                return None;
+            };
+            let log_start = *self.log_start.get_or_insert(frame.timestamp);
+            let offset = frame.timestamp - log_start;
+            if offset < self.options.start_s {
+                continue;
+            }
+            if self.options.end_s.is_some_and(|end| offset > end) {
+                if self.restart() {
+                    continue;
+                }
+                return None;
+            }
+            break frame;
+        };
+
+        frame.timestamp += self.loop_offset;
+        self.last_timestamp = frame.timestamp;
+        self.played = true;
+
+        if self.options.speed > 0.0 {
+            let (wall, log) = *self
+                .wall_start
+                .get_or_insert((Instant::now(), frame.timestamp));
+            let due = wall + Duration::from_secs_f64((frame.timestamp - log) / self.options.speed);
+            let now = Instant::now();
+            if due > now {
+                std::thread::sleep(due - now);
+            }
+        }
+        Some(frame)
+    }
+
+    /// Goes back to the start of the log if looping
+    fn restart(&mut self) -> bool {
+        if !self.options.looped || !self.played {
+            return false;
+        }
+        let Ok(reader) = LogReader::open(&self.path) else {
+            return false;
+        };
+        self.reader = reader;
+        self.played = false;
+        // Next loop's first frame lands just after this one's last
+        let first = self.log_start.unwrap() + self.options.start_s;
+        self.loop_offset = self.last_timestamp - first + 0.001;
+        true
+    }
+}
@@ -0,0 +1,589 @@
+// Turns a DBC into the Arrow schema and the (empty) columns behind it, plus the
+// bookkeeping data_loop needs to fill them one row at a time.
+// Also home to the DBC watcher, since a reload is just building all of this again.
+
+use dbc_rs::{ByteOrder, Dbc, Signal};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+
+use crate::FloatExt;
+use crate::args::{Aggregation, NamingPolicy};
+use crate::bits::{raw_value, signal_bits};
+use crate::dbcextra::{DbcExtras, ValueTable, raw_id};
+use crate::derived::DerivedEngine;
+use crate::store::{self, Column, GenericColumn};
+
+/// (path, contents) of one DBC file
+pub type DbcSource = (String, String);
+
+pub fn read_sources(paths: &[String]) -> Result<Vec<DbcSource>, String> {
+    paths
+        .iter()
+        .map(|p| {
+            fs::read_to_string(p)
+                .map(|c| (p.clone(), c))
+                .map_err(|e| format!("{}: {}", p, e))
+        })
+        .collect()
+}
+
+/// How DBC signals turn into column names. Whatever comes out of this is the name used
+/// everywhere else too: Parquet, --plot, alarm rules and derived signal expressions.
+#[derive(Default, Clone)]
+pub struct Naming {
+    pub policy: NamingPolicy,
+    pub renames: HashMap<String, String>, // "Message.Signal" or "Signal" -> column name
+}
+
+impl Naming {
+    /// Rename file is one `Message.Signal = NewName` (or `Signal = NewName`) per line
+    pub fn load(policy: NamingPolicy, rename_file: &str) -> Result<Self, String> {
+        let mut renames = HashMap::new();
+        if !rename_file.is_empty() {
+            let content =
+                fs::read_to_string(rename_file).map_err(|e| format!("{}: {}", rename_file, e))?;
+            for (lineno, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (from, to) = line.split_once('=').ok_or_else(|| {
+                    format!(
+                        "{}:{}: expected `Message.Signal = Name`",
+                        rename_file,
+                        lineno + 1
+                    )
+                })?;
+                renames.insert(from.trim().to_string(), to.trim().to_string());
+            }
+        }
+        Ok(Self { policy, renames })
+    }
+
+    /// `occurrences` is how many messages have a signal called that
+    /// Column a signal ends up in, `occurrences` is how many messages have a signal by that name
+    pub fn column_name(&self, message: &str, signal: &str, occurrences: usize) -> String {
+        let qualified = format!("{}.{}", message, signal);
+        if let Some(name) = self.renames.get(&qualified).or(self.renames.get(signal)) {
+            return name.clone();
+        }
+        match self.policy {
+            NamingPolicy::Plain => signal.to_string(),
+            NamingPolicy::Message => qualified,
+            NamingPolicy::Collision if occurrences > 1 => qualified,
+            NamingPolicy::Collision => signal.to_string(),
+        }
+    }
+}
+
+/// Where one decoded signal goes, worked out once when the schema is built so the
+/// receive loop doesn't have to look anything up by name
+pub struct SignalPlan {
+    pub signal: String,           // Name in the DBC, what decode() hands back
+    pub name: String,             // Column name, see Naming
+    pub col: usize,               // Column index in the schema
+    pub label_col: Option<usize>, // Its VAL_ label column, if any
+    pub plot: bool,               // Sent to the plot window
+    pub agg: Aggregation,         // What to keep when it shows up more than once in a chunk
+    pub float: Option<FloatPlan>, // Set for IEEE float signals
+}
+
+/// IEEE float signals (SIG_VALTYPE_) get decoded here from the raw bits, so it doesn't matter
+/// what dbc_rs makes of them
+pub struct FloatPlan {
+    bits: Vec<usize>,
+    big_endian: bool,
+    factor: f64,
+    offset: f64,
+}
+
+impl FloatPlan {
+    fn new(signal: &Signal) -> Self {
+        let big_endian = matches!(signal.byte_order(), ByteOrder::BigEndian);
+        Self {
+            bits: signal_bits(
+                signal.start_bit() as usize,
+                signal.length() as usize,
+                big_endian,
+            ),
+            big_endian,
+            factor: signal.factor(),
+            offset: signal.offset(),
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> f64 {
+        let raw = raw_value(data, &self.bits, self.big_endian);
+        let value = if self.bits.len() == 32 {
+            f64::from(f32::from_bits(raw as u32))
+        } else {
+            f64::from_bits(raw)
+        };
+        value * self.factor + self.offset
+    }
+}
+
+/// Everything needed to decode one CAN id
+pub struct MessagePlan {
+    pub name: String,
+    pub dbc: usize,               // Which of the dbcs defines it
+    pub signals: Vec<SignalPlan>, // In the order the DBC lists them
+}
+
+impl MessagePlan {
+    /// decode() returns signals in DBC order, so the position almost always hits first try
+    pub fn signal(&self, position: usize, name: &str) -> Option<&SignalPlan> {
+        match self.signals.get(position) {
+            Some(plan) if plan.signal == name => Some(plan),
+            _ => self.signals.iter().find(|p| p.signal == name),
+        }
+    }
+}
+
+/// The row being filled and everything before it that hasn't been written yet
+pub struct Rows {
+    pub columns: Vec<GenericColumn>, // This vec actually stores the values
+    pub is_filled: Vec<bool>, // This will keep track of which values have been filled so ones which haven't can be null balanced
+}
+
+/// What the plot window gets to know about a column besides its values
+#[derive(Default, Clone)]
+pub struct SignalInfo {
+    pub unit: String,
+    pub range: Option<(f64, f64)>, // DBC [min|max], None if the DBC leaves it at [0|0]
+    pub values: Option<ValueTable>, // VAL_ descriptions
+}
+
+pub struct Tables {
+    pub dbcs: Vec<Dbc>,
+    pub extras: DbcExtras,
+    pub schema: Arc<Schema>,
+    pub plan: HashMap<u32, MessagePlan>, // Raw message id -> decode plan
+    pub derived_plan: Vec<SignalPlan>,   // Same order as DerivedEngine::signals
+    pub rows: Rows,
+}
+
+/// Parses the DBCs and lays out one column per signal, then one per derived signal.
+/// Several files get merged into one set of messages, as long as they don't step on each other.
+pub fn build(
+    sources: &[DbcSource],
+    naming: &Naming,
+    derived: Option<&DerivedEngine>,
+    plots: &[String],
+    agg: Aggregation,
+) -> Result<Tables, String> {
+    let mut dbcs = Vec::new();
+    let mut extras = DbcExtras::default();
+    for (path, content) in sources {
+        dbcs.push(Dbc::parse(content).map_err(|e| format!("{}: {}", path, e))?); // Parse DBC
+        extras.merge(DbcExtras::parse(content));
+    }
+
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for dbc in dbcs.iter() {
+        for message in dbc.messages().iter() {
+            for signal in message.signals().iter() {
+                *occurrences.entry(signal.name().to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    // Same id in two files, or two signals ending up with the same column name, would silently mix data
+    let mut dbc_by_id: HashMap<u32, usize> = HashMap::new();
+    let mut column_owner: HashMap<String, String> = HashMap::new(); // column -> "file (message)"
+    let mut conflicts = Vec::new();
+    for (idx, dbc) in dbcs.iter().enumerate() {
+        let path = &sources[idx].0;
+        for message in dbc.messages().iter() {
+            if let Some(other) = dbc_by_id.insert(raw_id(message.id()), idx) {
+                conflicts.push(format!(
+                    "message id {:#x} ({}) is defined in both {} and {}",
+                    raw_id(message.id()),
+                    message.name(),
+                    sources[other].0,
+                    path
+                ));
+            }
+            for signal in message.signals().iter() {
+                let column =
+                    naming.column_name(message.name(), signal.name(), occurrences[signal.name()]);
+                let owner = format!("{} ({}.{})", path, message.name(), signal.name());
+                if let Some(other) = column_owner.insert(column.clone(), owner.clone()) {
+                    conflicts.push(format!(
+                        "column {} would come from both {} and {} (try --naming collision or --rename)",
+                        column, other, owner
+                    ));
+                }
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(format!("DBC conflicts:\n  {}", conflicts.join("\n  ")));
+    }
+
+    let mut base_row_size = 0; // Just to generate a cool "uncompressed data rate" number
+    let mut fields: Vec<Field> = Vec::new(); // vec of column descriptions, later dumped into Arc<Schema>
+    let mut columns: Vec<GenericColumn> = Vec::new(); // This vec actually stores the values
+    let mut is_filled: Vec<bool> = Vec::new(); // This will keep track of which values have been filled so ones which haven't can be null balanced
+
+    fields.push(Field::new(
+        "Time_ms",
+        DataType::Float64,
+        false, // Time is the only column that must exist in all rows.
+    ));
+
+    columns.push(GenericColumn::F64(Column::new())); // Column for time
+
+    is_filled.push(true); // This element of the map won't actually be used, but is needed for indecies to align
+
+    let mut plan: HashMap<u32, MessagePlan> = HashMap::new();
+
+    for (idx, dbc) in dbcs.iter().enumerate() {
+        // Every column remembers which file it came from
+        let source = HashMap::from([("dbc_file".to_string(), sources[idx].0.clone())]);
+        for message in dbc.messages().iter() {
+            let mut signals = Vec::new();
+            for signal in message.signals().iter() {
+                is_filled.push(false); // If I ever update this to exclude ANY signals which are present in the DBC, I will need to move this into the blocks below
+                let column =
+                    naming.column_name(message.name(), signal.name(), occurrences[signal.name()]);
+                let float = extras.is_float(message.id(), signal.name());
+                let (data_type, bits) = column_type(signal, float);
+                base_row_size += bits;
+                columns.push(GenericColumn::new_for(&data_type));
+                fields.push(Field::new(&column, data_type, true).with_metadata(source.clone()));
+                let col = columns.len() - 1;
+
+                // Enum signals keep the raw number and get a <name>_label dictionary column next to it
+                let mut label_col = None;
+                if let Some(table) = extras.value_table(message.id(), signal.name()) {
+                    label_col = Some(columns.len());
+                    is_filled.push(false);
+                    base_row_size += 16;
+                    fields.push(
+                        Field::new(
+                            format!("{}_label", column),
+                            DataType::Dictionary(
+                                Box::new(DataType::Int16),
+                                Box::new(DataType::Utf8),
+                            ),
+                            true,
+                        )
+                        .with_metadata(source.clone()),
+                    );
+                    columns.push(GenericColumn::Label(Column::new(), Arc::new(table.clone())));
+                }
+
+                signals.push(SignalPlan {
+                    signal: signal.name().to_string(),
+                    plot: plots.contains(&column),
+                    name: column,
+                    col,
+                    label_col,
+                    agg,
+                    float: float.then(|| FloatPlan::new(signal)),
+                });
+            }
+            plan.insert(
+                raw_id(message.id()),
+                MessagePlan {
+                    name: message.name().to_string(),
+                    dbc: idx,
+                    signals,
+                },
+            );
+        }
+    }
+    // Derived signals always get an f64 column tacked on after the DBC ones
+    let mut derived_plan = Vec::new();
+    if let Some(derived) = derived {
+        for d in derived.signals.iter() {
+            if fields.iter().any(|f| f.name() == &d.name) {
+                return Err(format!(
+                    "Derived signal {} has the same name as a DBC signal",
+                    d.name
+                ));
+            }
+            // A typo would otherwise leave the column null for the whole run
+            for input in d.inputs() {
+                if !column_owner.contains_key(&input)
+                    && !derived.signals.iter().any(|s| s.name == input)
+                {
+                    return Err(format!(
+                        "Derived signal {} uses {}, which isn't a DBC signal or derived signal",
+                        d.name, input
+                    ));
+                }
+            }
+            is_filled.push(false);
+            base_row_size += 64;
+            fields.push(Field::new(&d.name, DataType::Float64, true));
+            columns.push(GenericColumn::F64(Column::new()));
+            derived_plan.push(SignalPlan {
+                signal: d.name.clone(),
+                name: d.name.clone(),
+                col: columns.len() - 1,
+                label_col: None,
+                plot: plots.contains(&d.name),
+                agg,
+                float: None,
+            });
+        }
+    }
+    println!("\nBasis row size: {} bits", base_row_size);
+
+    Ok(Tables {
+        dbcs,
+        extras,
+        schema: Arc::new(Schema::new(fields)),
+        plan,
+        derived_plan,
+        rows: Rows { columns, is_filled },
+    })
+}
+
+/// The Arrow type a signal gets stored as, and its uncompressed size in bits
+pub fn column_type(signal: &Signal, float: bool) -> (DataType, usize) {
+    if float {
+        // IEEE float or double, whatever the factor
+        if signal.length() == 32 {
+            (DataType::Float32, 32)
+        } else {
+            (DataType::Float64, 64)
+        }
+    } else if signal.length() == 1
+        && signal.is_unsigned()
+        && signal.factor().is_nearly(1.0)
+        && signal.offset().is_nearly(0.0)
+    {
+        // Definetely a boolean
+        (DataType::Boolean, 1)
+    } else if (signal.factor() % 1.0).is_nearly(0.0) && (signal.offset() % 1.0).is_nearly(0.0) {
+        // Definetely an integer. Sized by what the raw bits can hold, the DBC's [min|max] is often
+        // left at [0|0] or just wrong, and anything outside the type would get clamped.
+        let (min, max) = physical_range(
+            signal.length() as u32,
+            signal.is_unsigned(),
+            signal.factor(),
+            signal.offset(),
+        )
+        .unwrap_or((signal.min(), signal.max()));
+        if min >= f64::from(i8::MIN) && max <= f64::from(i8::MAX) {
+            // Fits in i8
+            (DataType::Int8, 8)
+        } else if min >= f64::from(i16::MIN) && max <= f64::from(i16::MAX) {
+            // Fits in i16
+            (DataType::Int16, 16)
+        } else if min >= f64::from(i32::MIN) && max <= f64::from(i32::MAX) {
+            // Fits in i32
+            (DataType::Int32, 32)
+        } else {
+            // must fits in i64 :shrug
+            (DataType::Int64, 64)
+        }
+    } else {
+        // Float
+        //                if signal.min() >= f64::from(f16::MIN) && signal.max() <= f64::from(f16::MAX) {   // Fits in f16 (Currently only works in rust-unstable
+        //                    print!("f16");
+        //                    base_row_size+=16;
+        //                    fields.push(Field::new(signal.name(), DataType::Float16, true));
+        //                }
+
+        if signal.min() >= f64::from(f32::MIN) && signal.max() <= f64::from(f32::MAX) {
+            (DataType::Float32, 32)
+        } else {
+            // Must fits in f64 :shrug
+            (DataType::Float64, 64)
+        }
+    }
+}
+
+/// Smallest and largest physical value a signal's raw bits can represent
+pub fn physical_range(length: u32, unsigned: bool, factor: f64, offset: f64) -> Option<(f64, f64)> {
+    if length == 0 || length > 64 {
+        return None;
+    }
+    let (raw_min, raw_max) = if unsigned {
+        (0.0, 2f64.powi(length as i32) - 1.0)
+    } else {
+        (
+            -(2f64.powi(length as i32 - 1)),
+            2f64.powi(length as i32 - 1) - 1.0,
+        )
+    };
+    let a = raw_min * factor + offset;
+    let b = raw_max * factor + offset;
+    Some((a.min(b), a.max(b)))
+}
+
+impl Tables {
+    /// Unit, DBC range and VAL_ table of every signal, keyed by column name, for the plot window
+    pub fn signal_info(&self) -> HashMap<String, SignalInfo> {
+        let mut out = HashMap::new();
+        for dbc in &self.dbcs {
+            for message in dbc.messages().iter() {
+                let id = raw_id(message.id());
+                let Some(plan) = self.plan.get(&id) else {
+                    continue;
+                };
+                for (position, signal) in message.signals().iter().enumerate() {
+                    let Some(sp) = plan.signal(position, signal.name()) else {
+                        continue;
+                    };
+                    let key = (id, signal.name().to_string());
+                    out.insert(
+                        sp.name.clone(),
+                        SignalInfo {
+                            unit: self.extras.units.get(&key).cloned().unwrap_or_default(),
+                            range: (signal.max() > signal.min())
+                                .then_some((signal.min(), signal.max())),
+                            values: self.extras.value_tables.get(&key).cloned(),
+                        },
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    /// (node, message, column names) for the plot window's signal browser, sorted by node.
+    /// Derived signals are listed under a "Derived" node of their own.
+    pub fn catalog(&self) -> Vec<(String, String, Vec<String>)> {
+        let mut out: Vec<(String, String, Vec<String>)> = self
+            .plan
+            .iter()
+            .map(|(id, message)| {
+                let node = self.extras.senders.get(id).cloned().unwrap_or_default();
+                let columns = message.signals.iter().map(|s| s.name.clone()).collect();
+                (node, message.name.clone(), columns)
+            })
+            .collect();
+        out.sort();
+        if !self.derived_plan.is_empty() {
+            let columns = self.derived_plan.iter().map(|s| s.name.clone()).collect();
+            out.push(("Derived".to_string(), "Derived".to_string(), columns));
+        }
+        out
+    }
+
+    /// Changes which columns go to the plot window, from the signal browser
+    pub fn set_plotted(&mut self, plotted: &[String]) {
+        let signals = self.plan.values_mut().flat_map(|m| m.signals.iter_mut());
+        for signal in signals.chain(self.derived_plan.iter_mut()) {
+            signal.plot = plotted.contains(&signal.name);
+        }
+    }
+
+    /// Hands over every closed row as a batch and starts over with empty columns
+    pub fn take_batch(&mut self) -> RecordBatch {
+        let fresh = self.rows.columns.iter().map(|c| c.new_like()).collect();
+        let columns = std::mem::replace(&mut self.rows.columns, fresh);
+        store::finish_record_batch(columns, self.schema.clone())
+    }
+}
+
+impl Rows {
+    /// Puts a value into the current row, following the signal's aggregation if it already has one.
+    /// Returns true if this was the first value for the column in this row.
+    pub fn set(
+        &mut self,
+        col: usize,
+        label_col: Option<usize>,
+        agg: Aggregation,
+        value: f64,
+    ) -> bool {
+        let first = !self.is_filled[col];
+        if first {
+            self.columns[col].push_value(value);
+            self.is_filled[col] = true;
+        } else {
+            let replace = match (agg, self.columns[col].last_f64()) {
+                (Aggregation::First, _) => false,
+                (Aggregation::Last, _) | (_, None) => true,
+                (Aggregation::Min, Some(old)) => value < old,
+                (Aggregation::Max, Some(old)) => value > old,
+            };
+            if !replace {
+                return false;
+            }
+            self.columns[col].replace_last(value);
+        }
+
+        if let Some(label_col) = label_col {
+            if first {
+                self.columns[label_col].push_value(value);
+                self.is_filled[label_col] = true;
+            } else {
+                self.columns[label_col].replace_last(value);
+            }
+        }
+        first
+    }
+
+    /// Finishes the current row: stamps the time and null-fills everything that didn't get a value
+    pub fn close_row(&mut self, time_ms: f64) {
+        if let GenericColumn::F64(c) = &mut self.columns[0] {
+            c.push(Some(time_ms));
+        }
+        self.is_filled[0] = true;
+
+        for (index, value) in self.is_filled.iter().enumerate() {
+            if !value {
+                self.columns[index].push_null();
+            }
+        }
+
+        self.is_filled.fill(false);
+    }
+
+    /// True if anything other than time has been filled since the last row was closed
+    pub fn row_started(&self) -> bool {
+        self.is_filled[1..].iter().any(|f| *f)
+    }
+
+    /// Rows closed so far
+    pub fn count(&self) -> usize {
+        self.columns[0].len()
+    }
+}
+
+/// Polls the DBCs' modification times, at most once a second
+pub struct DbcWatcher {
+    paths: Vec<String>,
+    modified: Vec<Option<SystemTime>>,
+    last_check: Instant,
+}
+
+impl DbcWatcher {
+    pub fn new(paths: &[String]) -> Self {
+        Self {
+            paths: paths.to_vec(),
+            modified: paths.iter().map(|p| modified(p)).collect(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns all of the files again if any of them changed since last time
+    pub fn changed(&mut self) -> Option<Vec<DbcSource>> {
+        if self.last_check.elapsed() < Duration::from_secs(1) {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let now: Vec<Option<SystemTime>> = self.paths.iter().map(|p| modified(p)).collect();
+        if now == self.modified {
+            return None;
+        }
+        self.modified = now;
+        read_sources(&self.paths).ok()
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -4,6 +4,8 @@ use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
 
+#[allow(dead_code)] // Only the playback side of it is used here
+pub mod replay;
 pub mod socketwrap;
 
 // 1. Define the binary structure
@@ -20,44 +22,89 @@ struct CanFrame {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
+    // `sender --replay <log> <port> [--speed 2] [--loop] [--start s] [--end s]` serves a recorded log instead of a bus
+    let replay = args.get(1).is_some_and(|a| a == "--replay");
+    let positional = if replay { &args[2..] } else { &args[1..] };
+    if positional.len() < 2 || (!replay && positional.len() != 2) {
         eprintln!("Usage: {} <can_interface> <local_listen_port>", args[0]);
+        eprintln!(
+            "       {} --replay <log> <local_listen_port> [--speed x] [--loop] [--start s] [--end s]",
+            args[0]
+        );
         return Ok(());
     }
 
-    let can_interface = args[1].clone();
-    let local_port = format!("0.0.0.0:{}", args[2]);
+    let can_interface = positional[0].clone();
+    let local_port = format!("0.0.0.0:{}", positional[1]);
+
+    let mut options = replay::ReplayOptions::default();
+    let mut rest = positional[2..].iter().cloned();
+    while let Some(arg) = rest.next() {
+        if !options.parse_arg(&arg, &mut rest) {
+            eprintln!("Unknown argument: {}", arg);
+        }
+    }
 
     // 2. Broadcast channel for our struct
     let (tx, _) = broadcast::channel::<CanFrame>(100);
 
     // 3. CAN Polling Task
     let tx_can = tx.clone();
-    tokio::task::spawn_blocking(move || {
-        let mut cansocket = socketwrap::CanWrapper::new(&can_interface).unwrap();
-        println!("Polling CAN: {}", can_interface);
-
-        loop {
-            // 1. BLOCK and wait for a new frame from the hardware
-            if let Err(e) = cansocket.parse() {
-                eprintln!("CAN parse error: {}", e);
-                continue;
+    if replay {
+        tokio::task::spawn_blocking(move || {
+            let mut log = replay::Replay::open(&can_interface, options).unwrap();
+            println!("Replaying: {}", can_interface);
+
+            // Nobody would see the start of the log otherwise
+            while tx_can.receiver_count() == 0 {
+                std::thread::sleep(std::time::Duration::from_millis(100));
             }
 
-            // 2. NOW create the frame with the fresh data
-            let frame = CanFrame {
-                timestamp: cansocket.get_timestamp(),
-                id: cansocket.get_id(),
-                _pad: 0,
-                data: cansocket.get_data(),
-            };
-
-            // 3. Broadcast the unique frame
-            if tx_can.receiver_count() > 0 {
-                let _ = tx_can.send(frame);
+            // Same frames as a live bus, just with the log's timestamps
+            while let Some(logged) = log.next() {
+                let mut data = [0u8; 8];
+                let len = logged.data.len().min(8);
+                data[..len].copy_from_slice(&logged.data[..len]);
+
+                let frame = CanFrame {
+                    timestamp: logged.timestamp,
+                    id: logged.id,
+                    _pad: 0,
+                    data,
+                };
+                if tx_can.receiver_count() > 0 {
+                    let _ = tx_can.send(frame);
+                }
             }
-        }
-    });
+            println!("Replay finished");
+        });
+    } else {
+        tokio::task::spawn_blocking(move || {
+            let mut cansocket = socketwrap::CanWrapper::new(&can_interface).unwrap();
+            println!("Polling CAN: {}", can_interface);
+
+            loop {
+                // 1. BLOCK and wait for a new frame from the hardware
+                if let Err(e) = cansocket.parse() {
+                    eprintln!("CAN parse error: {}", e);
+                    continue;
+                }
+
+                // 2. NOW create the frame with the fresh data
+                let frame = CanFrame {
+                    timestamp: cansocket.get_timestamp(),
+                    id: cansocket.get_id(),
+                    _pad: 0,
+                    data: cansocket.get_data(),
+                };
+
+                // 3. Broadcast the unique frame
+                if tx_can.receiver_count() > 0 {
+                    let _ = tx_can.send(frame);
+                }
+            }
+        });
+    }
 
     // 4. TCP Server Task
     let listener = TcpListener::bind(&local_port).await?;
@@ -0,0 +1,306 @@
+// `--simulate`: makes up bus traffic from the DBC, so the logger, plots and alarms can be run on a
+// laptop or in CI with no CAN adapter. Every message goes out at its GenMsgCycleTime, with signal
+// values from generators. Same DBC, generator file and seed always give the same frames.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::time::{Duration, Instant};
+
+use arrow::array::{Array, Float64Array};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::encode::Encoder;
+use crate::replay::ReplayOptions;
+use crate::schema::DbcSource;
+use crate::source::Frame;
+
+pub enum Generator {
+    Const(f64),
+    Sine {
+        amplitude: f64,
+        period_ms: f64,
+        offset: f64,
+    },
+    Ramp {
+        from: f64,
+        to: f64,
+        period_ms: f64,
+    },
+    Walk {
+        step: f64,
+        min: f64,
+        max: f64,
+        value: f64,
+    },
+    Column(Vec<(f64, f64)>), // (Time_ms, value) from a logged Parquet file, loops at the end
+}
+
+impl Generator {
+    /// `sine(amplitude, period_ms, offset)`, `const(v)`, `ramp(from, to, period_ms)`,
+    /// `walk(step, min, max)` or `column(file.parquet, Column)`
+    fn parse(text: &str) -> Result<Self, String> {
+        let (func, rest) = text
+            .split_once('(')
+            .ok_or_else(|| format!("expected func(args), got {}", text))?;
+        let args: Vec<&str> = rest
+            .trim_end()
+            .strip_suffix(')')
+            .ok_or_else(|| format!("missing ) in {}", text))?
+            .split(',')
+            .map(|a| a.trim())
+            .collect();
+        let num = |i: usize| -> Result<f64, String> {
+            args.get(i)
+                .and_then(|a| a.parse().ok())
+                .ok_or_else(|| format!("{}: argument {} should be a number", text, i + 1))
+        };
+
+        match func.trim() {
+            "const" => Ok(Generator::Const(num(0)?)),
+            "sine" => Ok(Generator::Sine {
+                amplitude: num(0)?,
+                period_ms: num(1)?,
+                offset: num(2).unwrap_or(0.0),
+            }),
+            "ramp" => Ok(Generator::Ramp {
+                from: num(0)?,
+                to: num(1)?,
+                period_ms: num(2)?,
+            }),
+            "walk" => {
+                let (min, max) = (num(1)?, num(2)?);
+                Ok(Generator::Walk {
+                    step: num(0)?,
+                    min,
+                    max,
+                    value: (min + max) / 2.0,
+                })
+            }
+            "column" => match args.as_slice() {
+                [path, name] => Ok(Generator::Column(read_column(path, name)?)),
+                _ => Err(format!("{}: expected column(file.parquet, Column)", text)),
+            },
+            other => Err(format!("unknown generator {}", other)),
+        }
+    }
+
+    fn value(&mut self, time_ms: f64, rng: &mut u64) -> f64 {
+        match self {
+            Generator::Const(v) => *v,
+            Generator::Sine {
+                amplitude,
+                period_ms,
+                offset,
+            } => *offset + *amplitude * (std::f64::consts::TAU * time_ms / *period_ms).sin(),
+            Generator::Ramp {
+                from,
+                to,
+                period_ms,
+            } => *from + (*to - *from) * ((time_ms % *period_ms) / *period_ms),
+            Generator::Walk {
+                step,
+                min,
+                max,
+                value,
+            } => {
+                let r = next_random(rng) * 2.0 - 1.0;
+                *value = (*value + r * *step).clamp(*min, *max);
+                *value
+            }
+            Generator::Column(samples) => {
+                let Some(&(last_t, _)) = samples.last() else {
+                    return 0.0;
+                };
+                let t = if last_t > 0.0 {
+                    time_ms % last_t
+                } else {
+                    time_ms
+                };
+                // Last sample at or before t
+                let idx = samples.partition_point(|(st, _)| *st <= t);
+                samples[idx.saturating_sub(1)].1
+            }
+        }
+    }
+}
+
+/// Non-null (Time_ms, value) pairs of one column of a cantelope Parquet file
+fn read_column(path: &str, name: &str) -> Result<Vec<(f64, f64)>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|b| b.build())
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut samples = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| format!("{}: {}", path, e))?;
+        let as_f64 = |col: &str| -> Result<Float64Array, String> {
+            let array = batch
+                .column_by_name(col)
+                .ok_or_else(|| format!("{} has no column {}", path, col))?;
+            let array = cast(array, &DataType::Float64).map_err(|e| e.to_string())?;
+            Ok(array
+                .as_any()
+                .downcast_ref::<Float64Array>()
+                .unwrap()
+                .clone())
+        };
+        let times = as_f64("Time_ms")?;
+        let values = as_f64(name)?;
+        for i in 0..batch.num_rows() {
+            if !values.is_null(i) {
+                samples.push((times.value(i), values.value(i)));
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// xorshift64, in [0, 1). Plenty for wiggling test signals, and the same everywhere.
+fn next_random(state: &mut u64) -> f64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    (*state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+struct SimMessage {
+    name: String,
+    id: u32,
+    period_ms: f64,
+    due_ms: f64,
+    signals: Vec<(String, Option<usize>)>, // Signal name and its generator, if it has one
+}
+
+pub struct Simulator {
+    encoder: Encoder,
+    messages: Vec<SimMessage>,
+    generators: Vec<Generator>,
+    rng: u64,
+    options: ReplayOptions, // Only speed and end are used
+    wall_start: Instant,
+}
+
+impl Simulator {
+    /// Generator file is one `Signal = generator` (or `Message.Signal = generator`) per line.
+    /// Signals without one stay at 0, apart from the alive counters and CRCs the encoder fills in.
+    pub fn new(
+        sources: &[DbcSource],
+        generator_file: &str,
+        seed: u64,
+        options: ReplayOptions,
+    ) -> Result<Self, String> {
+        let encoder = Encoder::new(sources)?;
+
+        let mut by_name: HashMap<String, usize> = HashMap::new();
+        let mut lines: Vec<(String, usize)> = Vec::new(); // Name and line number, for the check below
+        let mut generators = Vec::new();
+        if !generator_file.is_empty() {
+            let content = fs::read_to_string(generator_file)
+                .map_err(|e| format!("{}: {}", generator_file, e))?;
+            for (lineno, line) in content.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or_default().trim(); // Comments
+                if line.is_empty() {
+                    continue;
+                }
+                let (name, generator) = line.split_once('=').ok_or_else(|| {
+                    format!(
+                        "{}:{}: expected `Signal = generator`",
+                        generator_file,
+                        lineno + 1
+                    )
+                })?;
+                let generator = Generator::parse(generator.trim())
+                    .map_err(|e| format!("{}:{}: {}", generator_file, lineno + 1, e))?;
+                by_name.insert(name.trim().to_string(), generators.len());
+                lines.push((name.trim().to_string(), lineno + 1));
+                generators.push(generator);
+            }
+        }
+
+        // A typo would leave the signal at 0 without a word, so every name has to be in the DBC
+        let messages = encoder.messages();
+        for (name, lineno) in &lines {
+            let known = messages.iter().any(|(message, _, signals)| {
+                signals
+                    .iter()
+                    .any(|s| s == name || format!("{}.{}", message, s) == *name)
+            });
+            if !known {
+                return Err(format!(
+                    "{}:{}: {} isn't a signal in the DBC",
+                    generator_file, lineno, name
+                ));
+            }
+        }
+
+        let messages = messages
+            .into_iter()
+            .map(|(name, id, signals)| {
+                let signals = signals
+                    .into_iter()
+                    .map(|s| {
+                        let generator = by_name
+                            .get(&format!("{}.{}", name, s))
+                            .or(by_name.get(&s))
+                            .copied();
+                        (s, generator)
+                    })
+                    .collect();
+                SimMessage {
+                    period_ms: f64::from(encoder.cycle_time_ms(id)),
+                    name,
+                    id,
+                    due_ms: 0.0,
+                    signals,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            encoder,
+            messages,
+            generators,
+            rng: seed.max(1), // xorshift gets stuck on 0
+            options,
+            wall_start: Instant::now(),
+        })
+    }
+
+    /// The next frame on the simulated bus, paced to --speed. None once --end is reached.
+    pub fn next(&mut self) -> Option<Frame> {
+        // Whichever message is due first, ties go in DBC order
+        let message = self
+            .messages
+            .iter_mut()
+            .min_by(|a, b| a.due_ms.total_cmp(&b.due_ms))?;
+        let time_ms = message.due_ms;
+        message.due_ms += message.period_ms;
+        if self.options.end_s.is_some_and(|end| time_ms > end * 1000.0) {
+            return None;
+        }
+
+        let mut values = HashMap::new();
+        for (signal, generator) in &message.signals {
+            if let Some(idx) = generator {
+                let value = self.generators[*idx].value(time_ms, &mut self.rng);
+                values.insert(signal.clone(), value);
+            }
+        }
+        let encoded = self
+            .encoder
+            .encode(&message.name, &values)
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        if self.options.speed > 0.0 {
+            let due =
+                self.wall_start + Duration::from_secs_f64(time_ms / 1000.0 / self.options.speed);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+        Some(Frame::new(time_ms / 1000.0, message.id, &encoded.data))
+    }
+}
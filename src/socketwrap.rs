@@ -1,5 +1,5 @@
-use socketcan::{CanFrame, CanSocket, EmbeddedFrame, Frame, Socket};
-use std::time::{SystemTime, UNIX_EPOCH};
+use socketcan::{CanFrame, CanSocket, EmbeddedFrame, ExtendedId, Frame, Socket, StandardId};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct CanWrapper {
     socket: CanSocket,
@@ -20,7 +20,13 @@ impl CanWrapper {
         })
     }
 
-    /// Blocks the current thread until the next packet is received
+    /// Makes parse give up after `timeout` with a WouldBlock/TimedOut error, so the caller gets a
+    /// chance to look at ctrl+c on a quiet bus
+    pub fn set_read_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Blocks the current thread until the next packet is received (or the read timeout, if set)
     pub fn parse(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let frame = self.socket.read_frame()?;
 
@@ -46,6 +52,25 @@ impl CanWrapper {
         Ok(())
     }
 
+    /// Puts one data frame on the bus. Blocks if the interface's transmit queue is full.
+    pub fn send(
+        &mut self,
+        id: u32,
+        extended: bool,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let frame = if extended {
+            let id = ExtendedId::new(id).ok_or("extended id out of range")?;
+            CanFrame::new(id, data)
+        } else {
+            let id = StandardId::new(id as u16).ok_or("standard id out of range")?;
+            CanFrame::new(id, data)
+        }
+        .ok_or("more than 8 data bytes")?;
+        self.socket.write_frame_insist(&frame)?;
+        Ok(())
+    }
+
     pub fn get_timestamp(&self) -> f64 {
         self.last_timestamp
     }
@@ -0,0 +1,193 @@
+// Everything frames can come from, behind one type so the receive stage doesn't care
+// whether it's reading a file, a pipe, a socket or the relay.
+
+use std::error::Error;
+use std::io::{self, Stdin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use candump::CanDumpParser;
+
+use crate::args::{Args, CanDataInput};
+use crate::replay::Replay;
+use crate::schema;
+use crate::simulate::Simulator;
+#[cfg(feature = "socket")]
+use crate::socketwrap::CanWrapper;
+use crate::tcpwrapper::TcpWrapper;
+
+/// How long a live source waits for a frame before looking at the stop flag again
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Frame {
+    pub timestamp: f64, // Seconds, on whatever clock the source uses
+    pub id: u32,
+    pub len: usize, // Candump frames can be shorter than 8 bytes, the rest of data is zero
+    pub data: [u8; 8],
+}
+
+impl Frame {
+    pub fn new(timestamp: f64, id: u32, bytes: &[u8]) -> Self {
+        let len = bytes.len().min(8);
+        let mut data = [0u8; 8];
+        data[..len].copy_from_slice(&bytes[..len]);
+        Self {
+            timestamp,
+            id,
+            len,
+            data,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+pub enum FrameSource {
+    File(CanDumpParser, bool), // bool is set once the parser has handed out its last frame
+    Stdin(CanDumpParser, Stdin, bool),
+    Replay(Replay),
+    Simulate(Box<Simulator>),
+    #[cfg(feature = "socket")]
+    Socket(CanWrapper),
+    Remote(TcpWrapper),
+}
+
+impl FrameSource {
+    /// Opens the input and reads the first frame, which only sets the start time
+    pub fn open(args: &Args, stop: &AtomicBool) -> (Self, f64) {
+        let mut source = match &args.candatainput {
+            CanDataInput::File => {
+                FrameSource::File(CanDumpParser::new(&args.input).unwrap(), false)
+            }
+            CanDataInput::Stdin => FrameSource::Stdin(
+                CanDumpParser::new(&String::default()).unwrap(),
+                io::stdin(),
+                false,
+            ),
+            CanDataInput::Replay => FrameSource::Replay(
+                Replay::open(&args.input, args.replay.clone())
+                    .unwrap_or_else(|e| panic!("{}: {}", args.input, e)),
+            ),
+            CanDataInput::Simulate => {
+                let sources =
+                    schema::read_sources(&args.dbcfiles).unwrap_or_else(|e| panic!("{}", e));
+                let simulator =
+                    Simulator::new(&sources, &args.input, args.seed, args.replay.clone())
+                        .unwrap_or_else(|e| panic!("Failed to set up the simulation: {}", e));
+                FrameSource::Simulate(Box::new(simulator))
+            }
+            #[cfg(feature = "socket")]
+            CanDataInput::Socket => {
+                let socket = CanWrapper::new(&args.input).unwrap();
+                socket.set_read_timeout(POLL).unwrap();
+                FrameSource::Socket(socket)
+            }
+            #[cfg(not(feature = "socket"))]
+            CanDataInput::Socket => {
+                panic!("Socketcan not enabled in this build")
+            }
+            CanDataInput::Remote => {
+                let tcp = TcpWrapper::new(&args.input);
+                tcp.set_read_timeout(POLL).unwrap();
+                FrameSource::Remote(tcp)
+            }
+        };
+        let time_start = source.next(stop).map(|f| f.timestamp).unwrap_or(0.0);
+        (source, time_start)
+    }
+
+    /// Blocks until the next frame, None once the source has run out. Live sources also give up
+    /// within POLL of `stop` being set, a quiet bus would keep them waiting forever otherwise.
+    pub fn next(&mut self, stop: &AtomicBool) -> Option<Frame> {
+        match self {
+            FrameSource::File(parser, finished) => {
+                if *finished {
+                    return None;
+                }
+                // The parser flags the end of the file along with the last frame, which still counts
+                *finished = parser.parse();
+                Some(candump_frame(parser))
+            }
+            FrameSource::Stdin(parser, stdin, finished) => {
+                if *finished {
+                    return None;
+                }
+                let mut nextline = String::new();
+                if stdin.read_line(&mut nextline).unwrap() == 0 {
+                    return None; // Whatever was piping in has closed
+                }
+                *finished = parser.parse_string(nextline);
+                Some(candump_frame(parser))
+            }
+            FrameSource::Replay(replay) => {
+                let frame = replay.next()?;
+                Some(Frame::new(frame.timestamp, frame.id, &frame.data))
+            }
+            FrameSource::Simulate(simulator) => simulator.next(),
+            #[cfg(feature = "socket")]
+            FrameSource::Socket(socket) => loop {
+                match socket.parse() {
+                    Ok(()) => {
+                        break Some(Frame::new(
+                            socket.get_timestamp(),
+                            socket.get_id(),
+                            &socket.get_data(),
+                        ));
+                    }
+                    Err(e) if timed_out(&*e) => {
+                        if stop.load(Ordering::SeqCst) {
+                            break None;
+                        }
+                    }
+                    Err(e) => panic!("{}", e),
+                }
+            },
+            FrameSource::Remote(tcp) => loop {
+                match tcp.parse() {
+                    Ok(()) => {
+                        break Some(Frame::new(
+                            tcp.get_timestamp(),
+                            tcp.get_id(),
+                            &tcp.get_data(),
+                        ));
+                    }
+                    Err(e) if timed_out(&e) && !stop.load(Ordering::SeqCst) => {}
+                    // The relay hanging up ends the run like the end of a file does
+                    Err(_) => break None,
+                }
+            },
+        }
+    }
+
+    /// A live bus doesn't wait for us, so when the decoder falls behind frames get dropped
+    /// (and counted) instead of stalling the read. Files and pipes can just wait.
+    pub fn is_live(&self) -> bool {
+        match self {
+            // Replay paces itself, but nothing is gained by dropping frames from a file
+            FrameSource::File(_, _)
+            | FrameSource::Stdin(_, _, _)
+            | FrameSource::Replay(_)
+            | FrameSource::Simulate(_) => false,
+            #[cfg(feature = "socket")]
+            FrameSource::Socket(_) => true,
+            FrameSource::Remote(_) => true,
+        }
+    }
+}
+
+/// A read timeout running out, as opposed to the source actually failing
+fn timed_out(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        )
+    })
+}
+
+fn candump_frame(parser: &CanDumpParser) -> Frame {
+    Frame::new(parser.get_timestamp(), parser.get_id(), &parser.get_data())
+}
@@ -9,6 +9,10 @@ use parquet::arrow::ArrowWriter;
 use parquet::file::properties::WriterProperties;
 use std::fs::File;
 
+use crate::FloatExt;
+use crate::alarm::AlarmEvent;
+use crate::dbcextra::ValueTable;
+
 pub struct Column<T> {
     values: Vec<T>,
     valid: Vec<bool>, // true = valid, false = null
@@ -38,6 +42,19 @@ impl<T: Default + Copy> Column<T> {
     pub fn len(&self) -> usize {
         self.values.len()
     }
+
+    pub fn pop(&mut self) {
+        self.values.pop();
+        self.valid.pop();
+    }
+
+    /// Last value, None if there isn't one or it's null
+    pub fn last(&self) -> Option<T> {
+        match self.valid.last() {
+            Some(true) => self.values.last().copied(),
+            _ => None,
+        }
+    }
 }
 
 pub enum GenericColumn {
@@ -49,6 +66,7 @@ pub enum GenericColumn {
     I16(Column<i16>),
     I32(Column<i32>),
     I64(Column<i64>),
+    Label(Column<i16>, Arc<ValueTable>), // Keys are positions in the VAL_ table, stored as a dictionary
 }
 
 impl GenericColumn {
@@ -61,6 +79,87 @@ impl GenericColumn {
             GenericColumn::I16(c) => c.push(None),
             GenericColumn::I32(c) => c.push(None),
             GenericColumn::I64(c) => c.push(None),
+            GenericColumn::Label(c, _) => c.push(None),
+        }
+    }
+
+    /// Pushes a decoded value, converted to whatever type this column stores
+    pub fn push_value(&mut self, value: f64) {
+        match self {
+            GenericColumn::Bool(c) => c.push(Some(value.is_nearly(1.0))),
+            GenericColumn::I8(c) => c.push(Some(value as i8)),
+            GenericColumn::I16(c) => c.push(Some(value as i16)),
+            GenericColumn::I32(c) => c.push(Some(value as i32)),
+            GenericColumn::I64(c) => c.push(Some(value as i64)),
+            //            GenericColumn::F16(c) => c.push(Some(f16::from(value))),
+            GenericColumn::F32(c) => c.push(Some(value as f32)),
+            GenericColumn::F64(c) => c.push(Some(value)),
+            GenericColumn::Label(_, _) => self.push_label(value),
+        }
+    }
+
+    /// Overwrites the value in the current row
+    pub fn replace_last(&mut self, value: f64) {
+        match self {
+            GenericColumn::Bool(c) => c.pop(),
+            GenericColumn::F64(c) => c.pop(),
+            GenericColumn::F32(c) => c.pop(),
+            GenericColumn::I8(c) => c.pop(),
+            GenericColumn::I16(c) => c.pop(),
+            GenericColumn::I32(c) => c.pop(),
+            GenericColumn::I64(c) => c.pop(),
+            GenericColumn::Label(c, _) => c.pop(),
+        }
+        self.push_value(value);
+    }
+
+    /// Value in the current row as a number, for min/max aggregation. Labels don't have one.
+    pub fn last_f64(&self) -> Option<f64> {
+        match self {
+            GenericColumn::Bool(c) => c.last().map(|v| if v { 1.0 } else { 0.0 }),
+            GenericColumn::F64(c) => c.last(),
+            GenericColumn::F32(c) => c.last().map(f64::from),
+            GenericColumn::I8(c) => c.last().map(f64::from),
+            GenericColumn::I16(c) => c.last().map(f64::from),
+            GenericColumn::I32(c) => c.last().map(f64::from),
+            GenericColumn::I64(c) => c.last().map(|v| v as f64),
+            GenericColumn::Label(_, _) => None,
+        }
+    }
+
+    /// An empty column for one of the types the schema builder hands out
+    pub fn new_for(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Boolean => GenericColumn::Bool(Column::new()),
+            DataType::Int8 => GenericColumn::I8(Column::new()),
+            DataType::Int16 => GenericColumn::I16(Column::new()),
+            DataType::Int32 => GenericColumn::I32(Column::new()),
+            DataType::Int64 => GenericColumn::I64(Column::new()),
+            DataType::Float32 => GenericColumn::F32(Column::new()),
+            _ => GenericColumn::F64(Column::new()),
+        }
+    }
+
+    /// An empty column of the same type
+    pub fn new_like(&self) -> Self {
+        match self {
+            GenericColumn::Bool(_) => GenericColumn::Bool(Column::new()),
+            GenericColumn::F64(_) => GenericColumn::F64(Column::new()),
+            GenericColumn::F32(_) => GenericColumn::F32(Column::new()),
+            GenericColumn::I8(_) => GenericColumn::I8(Column::new()),
+            GenericColumn::I16(_) => GenericColumn::I16(Column::new()),
+            GenericColumn::I32(_) => GenericColumn::I32(Column::new()),
+            GenericColumn::I64(_) => GenericColumn::I64(Column::new()),
+            GenericColumn::Label(_, table) => GenericColumn::Label(Column::new(), table.clone()),
+        }
+    }
+
+    /// Pushes the description matching a raw value, or null if the VAL_ table doesn't have it
+    pub fn push_label(&mut self, value: f64) {
+        if let GenericColumn::Label(c, table) = self {
+            let raw = value.round() as i64;
+            let key = table.iter().position(|(v, _)| *v == raw);
+            c.push(key.map(|k| k as i16));
         }
     }
 
@@ -73,6 +172,9 @@ impl GenericColumn {
             GenericColumn::I64(_) => DataType::Int64,
             GenericColumn::F32(_) => DataType::Float32,
             GenericColumn::F64(_) => DataType::Float64,
+            GenericColumn::Label(_, _) => {
+                DataType::Dictionary(Box::new(DataType::Int16), Box::new(DataType::Utf8))
+            }
         }
     }
 
@@ -85,6 +187,7 @@ impl GenericColumn {
             GenericColumn::I64(c) => c.len(),
             GenericColumn::F32(c) => c.len(),
             GenericColumn::F64(c) => c.len(),
+            GenericColumn::Label(c, _) => c.len(),
         }
     }
 
@@ -120,6 +223,12 @@ impl GenericColumn {
                 let null_buf = NullBuffer::from(c.valid);
                 Arc::new(Float64Array::new(c.values.into(), Some(null_buf)))
             }
+            GenericColumn::Label(c, table) => {
+                let null_buf = NullBuffer::from(c.valid);
+                let keys = Int16Array::new(c.values.into(), Some(null_buf));
+                let values = StringArray::from_iter_values(table.iter().map(|(_, d)| d.as_str()));
+                Arc::new(DictionaryArray::<Int16Type>::try_new(keys, Arc::new(values)).unwrap())
+            }
         }
     }
 }
@@ -142,16 +251,81 @@ pub fn write_record_batch_to_parquet(
     batch: &RecordBatch,
     path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let file = File::create(path)?;
+    let mut sink = ParquetSink::create(path, batch.schema())?;
+    sink.write(batch)?;
+    sink.close()
+}
+
+/// A Parquet file that stays open so batches can be appended as they fill up (one row group each)
+pub struct ParquetSink {
+    writer: ArrowWriter<File>,
+}
+
+impl ParquetSink {
+    pub fn create(path: &str, schema: Arc<Schema>) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = File::create(path)?;
+
+        let props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .build();
 
-    let props = WriterProperties::builder()
-        .set_compression(parquet::basic::Compression::SNAPPY)
-        .build();
+        let writer = ArrowWriter::try_new(file, schema, Some(props))?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.write(batch)?;
+        // Flush so a crash only loses the rows still in memory
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn close(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Output file for the nth DBC version. 0 is the path as given, then out.1.parquet, out.2.parquet...
+pub fn segment_path(output: &str, segment: usize) -> String {
+    if segment == 0 {
+        return output.to_string();
+    }
+    let stem = output.strip_suffix(".parquet").unwrap_or(output);
+    format!("{}.{}.parquet", stem, segment)
+}
+
+/// Writes the alarm event table. One row per alarm activation, end_ms is null if it never cleared
+/// (or hasn't yet). Rewritten whole every time, through a temp file so a crash mid-write leaves
+/// the previous version.
+pub fn write_alarm_events_to_parquet(
+    events: &[AlarmEvent],
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("rule", DataType::Utf8, false),
+        Field::new("signal", DataType::Utf8, false),
+        Field::new("start_ms", DataType::Float64, false),
+        Field::new("end_ms", DataType::Float64, true),
+    ]));
 
-    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))?;
+    let rules: StringArray = events.iter().map(|e| Some(e.rule.as_str())).collect();
+    let signals: StringArray = events.iter().map(|e| Some(e.signal.as_str())).collect();
+    let starts: Float64Array = events.iter().map(|e| Some(e.start_ms)).collect();
+    let ends: Float64Array = events.iter().map(|e| e.end_ms).collect();
 
-    writer.write(batch)?;
-    writer.close()?;
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(rules),
+            Arc::new(signals),
+            Arc::new(starts),
+            Arc::new(ends),
+        ],
+    )?;
 
+    let temp = format!("{}.tmp", path);
+    write_record_batch_to_parquet(&batch, &temp)?;
+    std::fs::rename(&temp, path)?;
     Ok(())
 }
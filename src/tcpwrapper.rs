@@ -1,6 +1,8 @@
 use bytemuck::{Pod, Zeroable};
-use std::io::Read;
-use std::net::TcpStream;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 // This must match the Relay's struct exactly
 #[repr(C)]
@@ -14,6 +16,8 @@ struct CanFrame {
 
 pub struct TcpWrapper {
     stream: TcpStream,
+    buffer: [u8; std::mem::size_of::<CanFrame>()],
+    filled: usize, // Bytes of the next frame already in buffer
     // Private variables to hold the "last parsed" state
     timestamp: f64,
     id: u32,
@@ -27,21 +31,36 @@ impl TcpWrapper {
 
         Self {
             stream,
+            buffer: [0; std::mem::size_of::<CanFrame>()],
+            filled: 0,
             timestamp: 0.0,
             id: 0,
             data: [0; 8],
         }
     }
 
+    /// Makes parse give up after `timeout` with a WouldBlock/TimedOut error, so the caller gets a
+    /// chance to look at ctrl+c while the relay is quiet
+    pub fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.stream.set_read_timeout(Some(timeout))
+    }
+
     /// Reads the next 24-byte frame from the network and updates internal state
     pub fn parse(&mut self) -> Result<(), std::io::Error> {
-        let mut buffer = [0u8; std::mem::size_of::<CanFrame>()];
-
-        // Read exactly 24 bytes
-        self.stream.read_exact(&mut buffer)?;
+        // Not read_exact, a timeout can land halfway through a frame and those bytes have to
+        // still be there for the next call
+        while self.filled < self.buffer.len() {
+            match self.stream.read(&mut self.buffer[self.filled..]) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.filled = 0;
 
         // Cast bytes back into our struct
-        let frame: CanFrame = *bytemuck::from_bytes(&buffer);
+        let frame: CanFrame = bytemuck::pod_read_unaligned(&self.buffer);
 
         // Update private variables
         self.timestamp = frame.timestamp;
@@ -63,3 +82,46 @@ impl TcpWrapper {
         self.data
     }
 }
+
+/// The sender's side of the protocol, for things in this binary that make their own frames
+pub struct TcpServer {
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl TcpServer {
+    /// Listens on all interfaces, clients get accepted in the background
+    pub fn bind(port: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", port))?;
+        let clients: Arc<Mutex<Vec<TcpStream>>> = Default::default();
+        let accepted = clients.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                _ = stream.set_nodelay(true);
+                println!("Client connected: {:?}", stream.peer_addr());
+                accepted.lock().unwrap().push(stream);
+            }
+        });
+        Ok(Self { clients })
+    }
+
+    pub fn wait_for_client(&self) {
+        while self.clients.lock().unwrap().is_empty() {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+
+    /// Sends to every connected client, dropping the ones that went away
+    pub fn send(&self, timestamp: f64, id: u32, data: [u8; 8]) {
+        let frame = CanFrame {
+            timestamp,
+            id,
+            _pad: 0,
+            data,
+        };
+        let bytes = bytemuck::bytes_of(&frame);
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| client.write_all(bytes).is_ok());
+    }
+}
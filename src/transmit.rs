@@ -0,0 +1,155 @@
+// `cantelope send --dbc fs.dbc MSG SIG=1.5 SIG2=3 --out can0`: builds a frame from signal values
+// with encode::Encoder and puts it on SocketCAN, or serves it to --remote clients in the sender's
+// TCP format. Once, or every --period-ms.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::args::Args;
+use crate::encode::{Encoded, Encoder};
+use crate::schema;
+#[cfg(feature = "socket")]
+use crate::socketwrap::CanWrapper;
+use crate::tcpwrapper::TcpServer;
+
+#[derive(Default, Clone, Debug)]
+pub struct SendOptions {
+    pub message: String, // Name or id
+    pub values: Vec<(String, f64)>,
+    pub period_ms: f64, // 0 sends once
+    pub count: usize,   // How many to send when periodic, 0 is until killed
+    pub out: String,    // SocketCAN interface
+    pub serve: String,  // TCP port
+}
+
+impl SendOptions {
+    /// Returns false if the argument isn't a send one
+    pub fn parse_arg(&mut self, arg: &str, rest: &mut impl Iterator<Item = String>) -> bool {
+        match arg {
+            "--period-ms" => {
+                self.period_ms = rest
+                    .next()
+                    .expect("--period-ms requires a value")
+                    .parse()
+                    .unwrap();
+            }
+            "--count" => {
+                self.count = rest
+                    .next()
+                    .expect("--count requires a value")
+                    .parse()
+                    .unwrap();
+            }
+            "--out" => self.out = rest.next().expect("--out requires a value"),
+            "--serve" => self.serve = rest.next().expect("--serve requires a value"),
+            _ => return false,
+        }
+        true
+    }
+
+    /// First one is the message, the rest are SIGNAL=value
+    pub fn positional(&mut self, arg: String) {
+        if self.message.is_empty() {
+            self.message = arg;
+            return;
+        }
+        let (name, value) = arg
+            .split_once('=')
+            .unwrap_or_else(|| panic!("Expected SIGNAL=value, got {}", arg));
+        let value = value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} isn't a number", value));
+        self.values.push((name.to_string(), value));
+    }
+}
+
+enum Output {
+    #[cfg(feature = "socket")]
+    Socket(CanWrapper),
+    Tcp(TcpServer),
+}
+
+impl Output {
+    fn send(&mut self, frame: &Encoded) {
+        match self {
+            #[cfg(feature = "socket")]
+            Output::Socket(socket) => {
+                if let Err(e) = socket.send(frame.id, frame.extended, &frame.data) {
+                    eprintln!("Send failed: {}", e);
+                }
+            }
+            Output::Tcp(server) => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs_f64();
+                let mut data = [0u8; 8];
+                let len = frame.data.len().min(8);
+                data[..len].copy_from_slice(&frame.data[..len]);
+                server.send(timestamp, frame.id, data);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "socket")]
+fn open_socket(interface: &str) -> Output {
+    Output::Socket(CanWrapper::new(interface).unwrap())
+}
+
+#[cfg(not(feature = "socket"))]
+fn open_socket(_interface: &str) -> Output {
+    panic!("Socketcan not enabled in this build")
+}
+
+pub fn run(args: &Args) {
+    let options = &args.send;
+    if options.message.is_empty() {
+        panic!("send needs a message: cantelope send --dbc x.dbc MESSAGE SIGNAL=value ...");
+    }
+
+    let sources = schema::read_sources(&args.dbcfiles).unwrap_or_else(|e| panic!("{}", e));
+    let mut encoder = Encoder::new(&sources).unwrap_or_else(|e| panic!("{}", e));
+    let values: HashMap<String, f64> = options.values.iter().cloned().collect();
+
+    let mut output = if !options.serve.is_empty() {
+        let server = TcpServer::bind(&options.serve).unwrap();
+        println!("Waiting for a client on port {}", options.serve);
+        server.wait_for_client();
+        Output::Tcp(server)
+    } else if !options.out.is_empty() {
+        open_socket(&options.out)
+    } else {
+        panic!("send needs somewhere to send to: --out <interface> or --serve <port>")
+    };
+
+    let periodic = options.period_ms > 0.0;
+    let count = if periodic { options.count } else { 1 };
+    let period = Duration::from_secs_f64(options.period_ms / 1000.0);
+    let mut next = Instant::now();
+    let mut sent = 0;
+    while count == 0 || sent < count {
+        // Encoded every time so alive counters and CRCs move
+        let frame = encoder
+            .encode(&options.message, &values)
+            .unwrap_or_else(|e| panic!("{}", e));
+        output.send(&frame);
+        sent += 1;
+
+        let hex: String = frame.data.iter().map(|b| format!("{:02X}", b)).collect();
+        if frame.extended {
+            println!("{:08X}#{}", frame.id, hex);
+        } else {
+            println!("{:03X}#{}", frame.id, hex);
+        }
+
+        if !periodic {
+            break;
+        }
+        // Scheduled from the start rather than the last send, so the period doesn't drift
+        next += period;
+        if let Some(wait) = next.checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    }
+}
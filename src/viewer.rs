@@ -0,0 +1,214 @@
+// `cantelope view run.parquet`: the plot window on a file cantelope wrote, instead of live data.
+// Every signal column gets loaded up front, so the whole session can be zoomed and panned and
+// picked from the signal browser. Pass the run's --dbc too for units, ranges and grouping by node.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::sync::{Arc, mpsc};
+
+use arrow::array::{Array, Float64Array, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+use crate::args::{Aggregation, Args};
+use crate::dashboard::Dashboard;
+use crate::dbcextra::ValueTable;
+use crate::plot::{self, Catalog, PlotWindow};
+use crate::plotbuf::{PlotStore, Ring};
+use crate::schema::{self, SignalInfo};
+use crate::workspace::Workspace;
+
+/// A logged session, one ring per signal column
+pub struct Session {
+    pub store: Arc<PlotStore>,
+    pub catalog: Catalog,
+    pub info: HashMap<String, SignalInfo>,
+    pub start: f64, // Time_ms of the first and last rows
+    pub end: f64,
+}
+
+impl Session {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .and_then(|b| b.build())
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let schema = reader.schema();
+        let batches: Vec<RecordBatch> = reader
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("{}: {}", path, e))?;
+        if schema.column_with_name("Time_ms").is_none() {
+            return Err(format!(
+                "{}: no Time_ms column, not a cantelope file?",
+                path
+            ));
+        }
+
+        let store: Arc<PlotStore> = Default::default();
+        let mut info: HashMap<String, SignalInfo> = HashMap::new();
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new(); // By DBC file
+        let (mut start, mut end) = (f64::INFINITY, f64::NEG_INFINITY);
+
+        for field in schema.fields().iter() {
+            let name = field.name();
+            if name == "Time_ms" || name.ends_with("_label") {
+                continue;
+            }
+
+            let mut points = Vec::new();
+            let mut values = ValueTable::new();
+            for batch in &batches {
+                let time = as_f64(batch, "Time_ms");
+                let column = as_f64(batch, name);
+                // Enum columns have their descriptions next to them, so get the VAL_ table back from those
+                let labels = batch
+                    .column_by_name(&format!("{}_label", name))
+                    .map(|l| cast(l, &DataType::Utf8).unwrap());
+                let labels = labels
+                    .as_ref()
+                    .map(|l| l.as_any().downcast_ref::<StringArray>().unwrap());
+
+                for row in 0..batch.num_rows() {
+                    if column.is_null(row) {
+                        continue;
+                    }
+                    let (x, y) = (time.value(row), column.value(row));
+                    start = start.min(x);
+                    end = end.max(x);
+                    points.push((x, y));
+                    if let Some(labels) = labels
+                        && labels.is_valid(row)
+                        && !values.iter().any(|(v, _)| *v == y.round() as i64)
+                    {
+                        values.push((y.round() as i64, labels.value(row).to_string()));
+                    }
+                }
+            }
+
+            store.insert(name, Ring::from_points(points));
+            if !values.is_empty() {
+                values.sort();
+                info.entry(name.clone()).or_default().values = Some(values);
+            }
+            let group = field
+                .metadata()
+                .get("dbc_file")
+                .cloned()
+                .unwrap_or_else(|| "Derived".to_string());
+            groups.entry(group).or_default().push(name.clone());
+        }
+
+        if !start.is_finite() {
+            (start, end) = (0.0, 0.0); // Nothing logged
+        }
+        store.set_latest(end);
+        let catalog = groups
+            .into_iter()
+            .map(|(group, columns)| (group, "Columns".to_string(), columns))
+            .collect();
+        Ok(Self {
+            store,
+            catalog,
+            info,
+            start,
+            end,
+        })
+    }
+
+    /// Units, ranges and node grouping from the DBC the file was logged with, for the columns it has
+    pub fn use_dbc(&mut self, args: &Args) -> Result<(), String> {
+        let sources = schema::read_sources(&args.dbcfiles)?;
+        let naming = schema::Naming::load(args.naming.clone(), &args.rename_file)?;
+        let tables = schema::build(&sources, &naming, None, &[], Aggregation::First)?;
+
+        let in_file = |name: &String| self.store.get(name).is_some();
+        for (name, dbc_info) in tables.signal_info() {
+            if in_file(&name) {
+                self.info.insert(name, dbc_info);
+            }
+        }
+        // Derived columns aren't in the DBC, they keep their group from the file
+        let derived: Vec<_> = self
+            .catalog
+            .drain(..)
+            .filter(|(g, _, _)| g == "Derived")
+            .collect();
+        self.catalog = tables
+            .catalog()
+            .into_iter()
+            .map(|(node, message, columns)| {
+                (
+                    node,
+                    message,
+                    columns.into_iter().filter(|c| in_file(c)).collect(),
+                )
+            })
+            .filter(|(_, _, columns): &(_, _, Vec<String>)| !columns.is_empty())
+            .chain(derived)
+            .collect();
+        Ok(())
+    }
+}
+
+/// Any numeric or bool column as f64
+fn as_f64(batch: &RecordBatch, name: &str) -> Float64Array {
+    let column = batch.column_by_name(name).unwrap();
+    cast(column, &DataType::Float64)
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap()
+        .clone()
+}
+
+pub fn run(args: &Args) {
+    let mut session = Session::load(&args.input).unwrap_or_else(|e| panic!("{}", e));
+    if !args.dbcfiles.is_empty() {
+        session
+            .use_dbc(args)
+            .unwrap_or_else(|e| panic!("Failed to load DBC: {}", e));
+    }
+    let workspace = if args.workspace_file.is_empty() {
+        Workspace::default()
+    } else {
+        Workspace::load(&args.workspace_file)
+            .unwrap_or_else(|e| panic!("Failed to load workspace: {}", e))
+    };
+    let dashboard = (!args.dashboard_file.is_empty()).then(|| {
+        Dashboard::load(&args.dashboard_file)
+            .unwrap_or_else(|e| panic!("Failed to load dashboard: {}", e))
+    });
+    println!(
+        "{}: {} signals over {:.1} s",
+        args.input,
+        session
+            .catalog
+            .iter()
+            .map(|(_, _, c)| c.len())
+            .sum::<usize>(),
+        (session.end - session.start) / 1000.0
+    );
+
+    let (plot_requests, _) = mpsc::channel(); // Everything's loaded already, nobody to ask
+    _ = PlotWindow::run(plot::Flags {
+        store: session.store,
+        plots: args.plots.clone(),
+        xy: args.xy_plots.clone(),
+        alarms: Default::default(),
+        info: session.info,
+        window_ms: (session.end - session.start).max(1.0), // All of it
+        catalog: session.catalog,
+        plot_requests,
+        dashboard,
+        workspace,
+        workspace_path: if args.workspace_file.is_empty() {
+            "workspace.toml".to_string()
+        } else {
+            args.workspace_file.clone()
+        },
+        offline: true,
+        stats: None,
+    });
+}
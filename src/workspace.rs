@@ -0,0 +1,88 @@
+// Plot layouts saved to a file, so a driver's or a test's setup doesn't have to be typed out
+// with --plot every session. TOML, ex:
+// ```
+// window_ms = 20000
+//
+// [colors]
+// BMS_PackVoltage = "#1f77b4"
+//
+// [[pane]]
+// traces = ["BMS_PackVoltage", "BMS_PackCurrent"]
+// left = [0, 600]      # Fixed Y axis, left out to follow the data
+//
+// [[pane]]
+// traces = ["SME_TRQSPD_Speed"]
+// dbc_range = true
+// ```
+// The plot window's "Save layout" button writes the current panes back to the same file, except the
+// ones from --plot and --xy.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use plotters::style::RGBColor;
+use serde::{Deserialize, Serialize};
+
+use crate::plot::Pane;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub window_ms: f64, // 0 leaves it to --window-ms or the default
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>, // Signal -> "#rrggbb", instead of the picked color
+    #[serde(default, rename = "pane")]
+    pub panes: Vec<Pane>,
+}
+
+impl Workspace {
+    /// Loads a workspace file. One that doesn't exist yet is an empty workspace, saving makes it.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {}", path, e)),
+        };
+        let workspace: Self = toml::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+        for (signal, color) in &workspace.colors {
+            if parse_color(color).is_none() {
+                return Err(format!(
+                    "{}: color {} for {} isn't #rrggbb",
+                    path, color, signal
+                ));
+            }
+        }
+        Ok(workspace)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Every signal in any pane
+    pub fn signals(&self) -> Vec<String> {
+        self.panes
+            .iter()
+            .flat_map(|p| p.signals().cloned())
+            .collect()
+    }
+
+    /// Colors from the file, parsed
+    pub fn palette(&self) -> BTreeMap<String, RGBColor> {
+        self.colors
+            .iter()
+            .filter_map(|(signal, color)| Some((signal.clone(), parse_color(color)?)))
+            .collect()
+    }
+}
+
+/// "#rrggbb"
+pub fn parse_color(color: &str) -> Option<RGBColor> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,55 @@
+// --aggregate: which of several values in one row ends up in the column. Voltage shows up four
+// times at t=0 (2, 1, 3 and 1.5 V), so the whole log is one row and every policy picks a
+// different value.
+mod common;
+use common::{column, fixture, run_logger, temp_path};
+
+const LOG: &str = "\
+(1700000000.000000) can0 12E#C800280000000000
+(1700000000.000000) can0 12E#6400280000000000
+(1700000000.000000) can0 12E#2C01280000000000
+(1700000000.000000) can0 12E#9600280000000000
+";
+
+/// Voltage in the single row when logged with this --aggregate
+fn voltage(aggregate: &str) -> Vec<Option<f64>> {
+    let test = format!("aggregate_{}", aggregate);
+    let log = temp_path(&test, "log");
+    std::fs::write(&log, LOG).unwrap();
+    let batch = run_logger(
+        &test,
+        &[
+            "--dbc",
+            &fixture("types.dbc"),
+            "--candump",
+            "-i",
+            &log,
+            "--cache-ms",
+            "1000",
+            "--aggregate",
+            aggregate,
+        ],
+    );
+    _ = std::fs::remove_file(&log);
+    column(&batch, "Voltage")
+}
+
+#[test]
+fn first() {
+    assert_eq!(voltage("first"), vec![Some(2.0)]);
+}
+
+#[test]
+fn last() {
+    assert_eq!(voltage("last"), vec![Some(1.5)]);
+}
+
+#[test]
+fn min() {
+    assert_eq!(voltage("min"), vec![Some(1.0)]);
+}
+
+#[test]
+fn max() {
+    assert_eq!(voltage("max"), vec![Some(3.0)]);
+}
@@ -0,0 +1,91 @@
+// Alarm rules on types.log and the event file they produce. Rpm goes 4660 -> 3000 at 500 ms,
+// Mode 2 -> 0 and Current -100 -> 50 at 600 ms.
+mod common;
+use common::{cantelope, column, fixture, labels, read_parquet, run_logger, temp_path};
+
+#[test]
+fn events() {
+    let rules = temp_path("alarms_events", "txt");
+    std::fs::write(
+        &rules,
+        "# Comments and blank lines are fine\n\
+         Rpm > 4000\n\
+         \n\
+         Mode == 2\n\
+         Current >= 0\n\
+         Voltage > 100 for 2s\n",
+    )
+    .unwrap();
+    let events = temp_path("alarms_events", "events.parquet");
+    run_logger(
+        "alarms_events",
+        &[
+            "--dbc",
+            &fixture("types.dbc"),
+            "--candump",
+            "-i",
+            &fixture("types.log"),
+            "--cache-ms",
+            "1000",
+            "--alarms",
+            &rules,
+            "--alarm-output",
+            &events,
+        ],
+    );
+    let batch = read_parquet(&events);
+    _ = std::fs::remove_file(&rules);
+    _ = std::fs::remove_file(&events);
+
+    // Voltage never stays up for 2 s, so three events, in the order they started
+    assert_eq!(
+        labels(&batch, "rule"),
+        vec![
+            Some("Rpm > 4000".to_string()),
+            Some("Mode == 2".to_string()),
+            Some("Current >= 0".to_string()),
+        ]
+    );
+    assert_eq!(
+        labels(&batch, "signal"),
+        vec![
+            Some("Rpm".to_string()),
+            Some("Mode".to_string()),
+            Some("Current".to_string()),
+        ]
+    );
+    let near = |got: Vec<Option<f64>>, want: &[Option<f64>]| {
+        assert_eq!(got.len(), want.len(), "{:?}", got);
+        for (g, w) in got.iter().zip(want) {
+            match (g, w) {
+                (Some(g), Some(w)) => assert!((g - w).abs() < 0.01, "{:?} vs {:?}", got, want),
+                _ => assert_eq!(g, w, "{:?} vs {:?}", got, want),
+            }
+        }
+    };
+    near(
+        column(&batch, "start_ms"),
+        &[Some(0.0), Some(0.0), Some(600.0)],
+    );
+    // Current is still above 0 when the log ends
+    near(column(&batch, "end_ms"), &[Some(500.0), Some(600.0), None]);
+}
+
+#[test]
+fn bad_rule() {
+    let rules = temp_path("alarms_bad", "txt");
+    std::fs::write(&rules, "Rpm > 4000\nRpm >> 5\n").unwrap();
+    let out = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--candump"])
+        .args(["-i", &fixture("types.log"), "--alarms", &rules])
+        .output()
+        .unwrap();
+    _ = std::fs::remove_file(&rules);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains(&format!("{}:2: bad threshold '> 5'", rules)),
+        "{}",
+        stderr
+    );
+}
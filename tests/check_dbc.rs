@@ -0,0 +1,106 @@
+mod common;
+use common::{cantelope, fixture};
+
+#[test]
+fn fs_dbc_passes() {
+    let out = cantelope()
+        .args(["check-dbc", &fixture("../../fs.dbc")])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    // IEEE floats (SIG_VALTYPE_) don't get the integer range check
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    for signal in ["VDM_GPS_Latitude", "VDM_GPS_Longitude"] {
+        let lines = report(&stdout, signal);
+        assert!(lines[0].ends_with("Float32"), "{}", lines[0]);
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+    }
+}
+
+#[test]
+fn mux_pages_may_share_bits() {
+    let status = cantelope()
+        .args(["check-dbc", &fixture("mux.dbc")])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// The lines printed under a signal: its column type, then one line per finding
+fn report<'a>(stdout: &'a str, signal: &str) -> Vec<&'a str> {
+    let mut lines = stdout
+        .lines()
+        .skip_while(|l| l.split_whitespace().next() != Some(signal));
+    let Some(first) = lines.next() else {
+        panic!("{} isn't in the report:\n{}", signal, stdout);
+    };
+    let mut out = vec![first.trim()];
+    out.extend(
+        lines
+            .take_while(|l| l.starts_with("      "))
+            .map(|l| l.trim()),
+    );
+    out
+}
+
+#[test]
+fn bad_dbc_fails() {
+    let out = cantelope()
+        .args(["check-dbc", &fixture("bad.dbc")])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+
+    // OVERLAP's First is fine on its own (12 bits, so an Int16), Second overlaps it in bits 8-11
+    let first = report(&stdout, "First");
+    assert_eq!(first.len(), 1, "{}", stdout);
+    assert!(first[0].ends_with("Int16"), "{}", first[0]);
+    assert_eq!(
+        report(&stdout, "Second")[1..],
+        ["ERROR: shares 4 bit(s) with First"]
+    );
+    assert_eq!(
+        report(&stdout, "Long")[1..],
+        ["ERROR: uses bit 23 but the message is only 2 bytes"]
+    );
+    let duplicate = stdout
+        .lines()
+        .filter(|l| l.contains("ERROR: name is also used in"))
+        .collect::<Vec<_>>();
+    assert_eq!(duplicate.len(), 1, "{}", stdout);
+    assert!(
+        duplicate[0].ends_with("bad.dbc (OVERLAP) (log with --naming or --rename to keep both)")
+    );
+
+    // 8 bits x 0.5 tops out at 127.5, and the type comes from the factor
+    let range = report(&stdout, "Range");
+    assert!(range[0].ends_with("Float32"), "{}", range[0]);
+    assert_eq!(
+        range[1..],
+        ["WARNING: range [0|1000] doesn't fit in 8 bits x 0.5 + 0 = [0|127.5]"]
+    );
+
+    assert!(stdout.contains("3 error(s), 1 warning(s)"), "{}", stdout);
+}
+
+#[test]
+fn naming_resolves_duplicates() {
+    let out = cantelope()
+        .args(["check-dbc", &fixture("bad.dbc"), "--naming", "collision"])
+        .output()
+        .unwrap();
+    // Still fails on the overlap and the DLC, but the duplicate gets its own columns now
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let duplicate = &stdout[stdout.find("DUPLICATE").unwrap()..];
+    let first = report(duplicate, "First");
+    assert!(
+        first[1].starts_with("WARNING: name is also used in")
+            && first[1].ends_with("(OVERLAP), logged as OVERLAP.First and DUPLICATE.First"),
+        "{:?}",
+        first
+    );
+    assert!(stdout.contains("2 error(s), 2 warning(s)"), "{}", stdout);
+}
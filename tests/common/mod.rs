@@ -0,0 +1,130 @@
+// Shared helpers for the integration tests. Everything goes through the real binary,
+// the same way it gets run on the car.
+#![allow(dead_code)] // Not every test file uses every helper
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::Command;
+
+use arrow::array::{Array, Float64Array, StringArray};
+use arrow::compute::{cast, concat_batches};
+use arrow::datatypes::DataType;
+use arrow::record_batch::{RecordBatch, RecordBatchReader};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+pub fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+/// Unique per test so tests can run in parallel
+pub fn temp_path(test: &str, ext: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("cantelope_{}_{}.{}", test, std::process::id(), ext))
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn cantelope() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_cantelope"))
+}
+
+/// Runs a candump file through the logger and returns everything it wrote
+pub fn run_candump(test: &str, dbc: &str, log: &str, cache_ms: &str) -> RecordBatch {
+    run_logger(
+        test,
+        &[
+            "--dbc",
+            &fixture(dbc),
+            "--candump",
+            "-i",
+            &fixture(log),
+            "--cache-ms",
+            cache_ms,
+        ],
+    )
+}
+
+/// Runs the logger with these arguments plus an --output, and returns everything it wrote
+pub fn run_logger(test: &str, args: &[&str]) -> RecordBatch {
+    let output = temp_path(test, "parquet");
+    let status = cantelope()
+        .args(args)
+        .args(["--output", &output])
+        .status()
+        .expect("failed to run cantelope");
+    assert!(status.success(), "cantelope exited with {}", status);
+
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+    batch
+}
+
+/// (timestamp, id, data) of every line of a candump -L fixture
+pub fn read_candump(name: &str) -> Vec<(f64, u32, Vec<u8>)> {
+    let content = std::fs::read_to_string(fixture(name)).unwrap();
+    content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|line| {
+            let mut parts = line.split_whitespace();
+            let timestamp = parts.next().unwrap().trim_matches(['(', ')']);
+            let (id, data) = parts.nth(1).unwrap().split_once('#').unwrap();
+            let data = (0..data.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&data[i..i + 2], 16).unwrap())
+                .collect();
+            (
+                timestamp.parse().unwrap(),
+                u32::from_str_radix(id, 16).unwrap(),
+                data,
+            )
+        })
+        .collect()
+}
+
+/// The relay's 24 byte frame: f64 timestamp, u32 id, 4 bytes of padding, 8 data bytes
+pub fn relay_frame(timestamp: f64, id: u32, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(24);
+    frame.extend_from_slice(&timestamp.to_ne_bytes());
+    frame.extend_from_slice(&id.to_ne_bytes());
+    frame.extend_from_slice(&[0; 4]);
+    let mut padded = [0u8; 8];
+    padded[..data.len()].copy_from_slice(data);
+    frame.extend_from_slice(&padded);
+    frame
+}
+
+pub fn read_parquet(path: &str) -> RecordBatch {
+    let file = File::open(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    let schema = reader.schema();
+    let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+    concat_batches(&schema, &batches).unwrap()
+}
+
+/// Any numeric or boolean column as f64s, so tests don't care what type the schema builder picked
+pub fn column(batch: &RecordBatch, name: &str) -> Vec<Option<f64>> {
+    let col = batch
+        .column_by_name(name)
+        .unwrap_or_else(|| panic!("no column {}", name));
+    let col = cast(col, &DataType::Float64).unwrap();
+    let col = col.as_any().downcast_ref::<Float64Array>().unwrap();
+    (0..col.len())
+        .map(|i| (!col.is_null(i)).then(|| col.value(i)))
+        .collect()
+}
+
+/// An enum signal's label column as strings
+pub fn labels(batch: &RecordBatch, name: &str) -> Vec<Option<String>> {
+    let col = batch
+        .column_by_name(name)
+        .unwrap_or_else(|| panic!("no column {}", name));
+    let col = cast(col, &DataType::Utf8).unwrap();
+    let col = col.as_any().downcast_ref::<StringArray>().unwrap();
+    (0..col.len())
+        .map(|i| (!col.is_null(i)).then(|| col.value(i).to_string()))
+        .collect()
+}
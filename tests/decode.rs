@@ -0,0 +1,140 @@
+// Golden values for the decode itself: byte order, sign, scaling, enums, IEEE floats and the schema
+// they land in.
+// The fixtures put one frame of each message in each row: the first row closes on the first frame
+// after t=0, the second one at the end of the file (--cache-ms 1000).
+mod common;
+use common::{column, labels, run_candump};
+
+use arrow::datatypes::DataType;
+
+#[test]
+fn types_schema() {
+    let batch = run_candump("types_schema", "types.dbc", "types.log", "1000");
+    let schema = batch.schema();
+    let fields: Vec<(&str, &DataType)> = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().as_str(), f.data_type()))
+        .collect();
+
+    let label = DataType::Dictionary(Box::new(DataType::Int16), Box::new(DataType::Utf8));
+    // Integer factor and offset get the smallest integer type the raw bits fit in
+    assert_eq!(
+        fields,
+        vec![
+            ("Time_ms", &DataType::Float64),
+            ("Rpm", &DataType::Int32),
+            ("Temp", &DataType::Float32),
+            ("Current", &DataType::Float32),
+            ("Small", &DataType::Int8),
+            ("Flag", &DataType::Boolean),
+            ("Mode", &DataType::Int8),
+            ("Mode_label", &label),
+            ("Voltage", &DataType::Float32),
+            ("Offset", &DataType::Float32),
+            ("Pressure", &DataType::Float32),
+            ("Gain", &DataType::Float32),
+        ]
+    );
+    assert!(!schema.field(0).is_nullable());
+    assert!(schema.fields().iter().skip(1).all(|f| f.is_nullable()));
+    assert!(schema.fields().iter().skip(1).all(|f| {
+        f.metadata()
+            .get("dbc_file")
+            .is_some_and(|p| p.ends_with("types.dbc"))
+    }));
+}
+
+#[test]
+fn big_endian() {
+    let batch = run_candump("types_big_endian", "types.dbc", "types.log", "1000");
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(column(&batch, "Rpm"), vec![Some(4660.0), Some(3000.0)]);
+    // 12 bits across a byte boundary, signed, with an offset
+    assert_eq!(column(&batch, "Temp"), vec![Some(-40.5), Some(-39.5)]);
+}
+
+#[test]
+fn signed() {
+    let batch = run_candump("types_signed", "types.dbc", "types.log", "1000");
+    assert_eq!(column(&batch, "Current"), vec![Some(-100.0), Some(50.0)]);
+    assert_eq!(column(&batch, "Small"), vec![Some(-3.0), Some(1.0)]);
+    assert_eq!(column(&batch, "Flag"), vec![Some(1.0), Some(0.0)]);
+}
+
+#[test]
+fn scaled() {
+    let batch = run_candump("types_scaled", "types.dbc", "types.log", "1000");
+    assert_eq!(column(&batch, "Voltage"), vec![Some(398.5), Some(0.0)]);
+    // Second frame is only 7 bytes long
+    assert_eq!(column(&batch, "Offset"), vec![Some(0.5), Some(0.0)]);
+}
+
+#[test]
+fn ieee_float() {
+    let batch = run_candump("types_float", "types.dbc", "types.log", "1000");
+    // 0x3FC00000 is 1.5, 0x447D5000 is 1013.25
+    assert_eq!(column(&batch, "Pressure"), vec![Some(1.5), Some(1013.25)]);
+    // Scaling applies on top: 0xBE800000 is -0.25 and 0x40400000 is 3.0, times 2 plus 1
+    assert_eq!(column(&batch, "Gain"), vec![Some(0.5), Some(7.0)]);
+}
+
+#[test]
+fn enum_labels() {
+    let batch = run_candump("types_enum", "types.dbc", "types.log", "1000");
+    assert_eq!(column(&batch, "Mode"), vec![Some(2.0), Some(0.0)]);
+    assert_eq!(
+        labels(&batch, "Mode_label"),
+        vec![Some("Run".to_string()), Some("Off".to_string())]
+    );
+}
+
+#[test]
+fn fs_dbc() {
+    let batch = run_candump("fs_golden", "../../fs.dbc", "fs.log", "1000");
+    assert_eq!(batch.num_rows(), 2);
+
+    assert_eq!(
+        batch
+            .schema()
+            .field_with_name("SME_THROTL_Forward")
+            .unwrap()
+            .data_type(),
+        &DataType::Boolean
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_TorqueDemand"),
+        vec![Some(-100.0), Some(100.0)]
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_MaxSpeed"),
+        vec![Some(3000.0), Some(0.0)]
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_Forward"),
+        vec![Some(1.0), Some(0.0)]
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_Reverse"),
+        vec![Some(0.0), Some(1.0)]
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_PowerReady"),
+        vec![Some(1.0), Some(0.0)]
+    );
+    assert_eq!(
+        column(&batch, "SME_THROTL_MBB_Alive"),
+        vec![Some(5.0), Some(12.0)]
+    );
+
+    // Only sent in the first row, the second one is null
+    assert_eq!(
+        column(&batch, "SME_CURRLIM_ChargeCurrentLim"),
+        vec![Some(120.0), None]
+    );
+    assert_eq!(
+        column(&batch, "SME_CURRLIM_DischargeCurrentLim"),
+        vec![Some(250.0), None]
+    );
+    assert_eq!(column(&batch, "SME_TRQSPD_Speed"), vec![None, None]);
+}
@@ -0,0 +1,112 @@
+// Derived signals end to end: expressions over decoded columns, and definitions that get refused
+// at startup instead of logging an empty column.
+mod common;
+use common::{cantelope, column, fixture, run_logger, temp_path};
+
+/// Writes the definitions to a temp file and returns its path
+fn derived_file(test: &str, content: &str) -> String {
+    let path = temp_path(test, "txt");
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn chained() {
+    let derived = derived_file("derived_chained", "Double = Rpm * 2\nQuad = Double * 2\n");
+    let batch = run_logger(
+        "derived_chained",
+        &[
+            "--dbc",
+            &fixture("types.dbc"),
+            "--candump",
+            "-i",
+            &fixture("types.log"),
+            "--cache-ms",
+            "1000",
+            "--derived",
+            &derived,
+        ],
+    );
+    _ = std::fs::remove_file(&derived);
+    assert_eq!(column(&batch, "Double"), vec![Some(9320.0), Some(6000.0)]);
+    assert_eq!(column(&batch, "Quad"), vec![Some(18640.0), Some(12000.0)]);
+}
+
+#[test]
+fn message_naming() {
+    let derived = derived_file("derived_naming", "Double = BIGEND.Rpm * 2\n");
+    let batch = run_logger(
+        "derived_naming",
+        &[
+            "--dbc",
+            &fixture("types.dbc"),
+            "--candump",
+            "-i",
+            &fixture("types.log"),
+            "--cache-ms",
+            "1000",
+            "--naming",
+            "message",
+            "--derived",
+            &derived,
+        ],
+    );
+    _ = std::fs::remove_file(&derived);
+    assert_eq!(
+        column(&batch, "BIGEND.Rpm"),
+        vec![Some(4660.0), Some(3000.0)]
+    );
+    assert_eq!(column(&batch, "Double"), vec![Some(9320.0), Some(6000.0)]);
+}
+
+/// Runs the logger on types.log with these definitions, which should be refused with this error
+fn refused(test: &str, content: &str, error: &str) {
+    let derived = derived_file(test, content);
+    let out = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--candump"])
+        .args(["-i", &fixture("types.log"), "--derived", &derived])
+        .args(["--output", &temp_path(test, "parquet")])
+        .output()
+        .unwrap();
+    _ = std::fs::remove_file(&derived);
+    assert!(
+        !out.status.success(),
+        "{:?} should have been refused",
+        content
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let error = error.replace("FILE", &derived);
+    assert!(
+        stderr.contains(&error),
+        "expected {:?} in:\n{}",
+        error,
+        stderr
+    );
+}
+
+#[test]
+fn unknown_input() {
+    refused(
+        "derived_unknown",
+        "Double = Rpmm * 2\n",
+        "Derived signal Double uses Rpmm, which isn't a DBC signal or derived signal",
+    );
+}
+
+#[test]
+fn forward_reference() {
+    refused(
+        "derived_forward",
+        "Quad = Double * 2\nDouble = Rpm * 2\n",
+        "FILE:1: Quad uses Double, which has to be defined above it",
+    );
+}
+
+#[test]
+fn self_reference() {
+    refused(
+        "derived_self",
+        "Double = Rpm * 2\nCounter = Counter + 1\n",
+        "FILE:2: Counter uses itself",
+    );
+}
@@ -0,0 +1,123 @@
+// `cantelope export` draws a logged file without a display, so it can be checked here.
+// Text layout needs a system font, same as on the logger.
+mod common;
+use common::{cantelope, fixture, temp_path};
+
+/// Logs types.log, exports it with these arguments, and returns the SVG
+fn export(test: &str, args: &[&str]) -> String {
+    let parquet = temp_path(test, "parquet");
+    let status = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--candump"])
+        .args(["-i", &fixture("types.log"), "--cache-ms", "1000"])
+        .args(["--output", &parquet])
+        .status()
+        .unwrap();
+    assert!(status.success(), "logging exited with {}", status);
+
+    let svg = temp_path(test, "svg");
+    let status = cantelope()
+        .args(["export", &parquet, "--dbc", &fixture("types.dbc")])
+        .args(args)
+        .args(["-o", &svg])
+        .status()
+        .unwrap();
+    _ = std::fs::remove_file(&parquet);
+    assert!(status.success(), "export exited with {}", status);
+
+    let content = std::fs::read_to_string(&svg).unwrap();
+    _ = std::fs::remove_file(&svg);
+    content
+}
+
+#[test]
+fn time_panes() {
+    let svg = export(
+        "export_time",
+        &[
+            "--plot",
+            "Rpm,Current",
+            "--plot",
+            "Voltage",
+            "--size",
+            "800x600",
+        ],
+    );
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains(r#"width="800""#));
+    // Legends, and the units on the axes
+    for text in ["Rpm", "Current", "Voltage", "rpm", ">A<", ">V<"] {
+        assert!(svg.contains(text), "no {} in the export", text);
+    }
+}
+
+#[test]
+fn xy_pane() {
+    let svg = export("export_xy", &["--xy", "Rpm,Voltage"]);
+    assert!(svg.contains("Voltage vs Rpm"));
+    assert!(svg.contains("<circle"));
+}
+
+#[test]
+fn nothing_to_draw() {
+    let parquet = temp_path("export_empty", "parquet");
+    let status = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--candump"])
+        .args(["-i", &fixture("types.log"), "--output", &parquet])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = cantelope()
+        .args(["export", &parquet, "-o", &temp_path("export_empty", "svg")])
+        .status()
+        .unwrap();
+    _ = std::fs::remove_file(&parquet);
+    assert!(!status.success(), "export without panes should fail");
+}
+
+#[test]
+fn while_logging() {
+    // Rpm climbing over six frames, so the trace has more than the two points an axis line has
+    let log = temp_path("export_live", "log");
+    let frames: String = (0..6)
+        .map(|i| {
+            format!(
+                "(1700000000.{}00000) can0 12C#{:04X}000000000000\n",
+                i,
+                1000 * (i + 1)
+            )
+        })
+        .collect();
+    std::fs::write(&log, frames).unwrap();
+
+    // --no-gui so it works without a display, like on the car
+    let (parquet, svg) = (
+        temp_path("export_live", "parquet"),
+        temp_path("export_live", "svg"),
+    );
+    let status = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--candump"])
+        .args(["-i", &log, "--output", &parquet])
+        .args(["--plot", "Rpm", "--export", &svg, "--no-gui"])
+        .status()
+        .unwrap();
+    _ = std::fs::remove_file(&parquet);
+    _ = std::fs::remove_file(&log);
+    assert!(status.success(), "logging exited with {}", status);
+
+    let content = std::fs::read_to_string(&svg).unwrap();
+    _ = std::fs::remove_file(&svg);
+    assert!(content.starts_with("<svg"));
+    assert!(content.contains("Rpm"));
+    let points = content
+        .split("<polyline")
+        .skip(1)
+        .filter_map(|p| p.split("points=\"").nth(1)?.split('"').next())
+        .map(|p| p.split_whitespace().count())
+        .max();
+    assert!(
+        points > Some(2),
+        "no trace drawn, longest polyline has {:?} points",
+        points
+    );
+}
@@ -0,0 +1,161 @@
+// Every CanDataInput should log the same thing as --candump does for the same frames.
+// --simulate makes up its own frames, so it's covered in simulate.rs instead.
+mod common;
+use common::{
+    cantelope, fixture, read_candump, read_parquet, relay_frame, run_candump, run_logger, temp_path,
+};
+
+use std::io::Write;
+use std::net::TcpListener;
+use std::process::Stdio;
+
+use arrow::record_batch::RecordBatch;
+
+const DBC: &str = "types.dbc";
+const LOG: &str = "types.log";
+
+fn golden() -> RecordBatch {
+    run_candump("inputs_golden", DBC, LOG, "1000")
+}
+
+#[test]
+fn stdin() {
+    let output = temp_path("inputs_stdin", "parquet");
+    let mut child = cantelope()
+        .args(["--dbc", &fixture(DBC), "--stdin", "--cache-ms", "1000"])
+        .args(["--output", &output])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let log = std::fs::read(fixture(LOG)).unwrap();
+    child.stdin.take().unwrap().write_all(&log).unwrap(); // Dropped here, which closes the pipe
+    let status = child.wait().unwrap();
+    assert!(status.success(), "cantelope exited with {}", status);
+
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+    assert_eq!(batch, golden());
+}
+
+fn replay(test: &str, log: &str, speed: &str) -> RecordBatch {
+    let dbc = fixture(DBC);
+    run_logger(
+        test,
+        &[
+            "--dbc",
+            &dbc,
+            "--replay",
+            "-i",
+            log,
+            "--speed",
+            speed,
+            "--cache-ms",
+            "1000",
+        ],
+    )
+}
+
+#[test]
+fn replay_candump() {
+    let golden = golden();
+    // Pacing only changes when frames arrive, not what gets logged
+    assert_eq!(replay("inputs_replay", &fixture(LOG), "1"), golden);
+    assert_eq!(replay("inputs_replay_max", &fixture(LOG), "max"), golden);
+}
+
+#[test]
+fn replay_raw_archive() {
+    let archive = temp_path("inputs_raw", "bin");
+    let raw: Vec<u8> = read_candump(LOG)
+        .iter()
+        .flat_map(|(timestamp, id, data)| relay_frame(*timestamp, *id, data))
+        .collect();
+    std::fs::write(&archive, raw).unwrap();
+
+    let batch = replay("inputs_raw", &archive, "max");
+    _ = std::fs::remove_file(&archive);
+
+    // Raw frames are always 8 bytes, the short frame's padding is zeros and decodes the same
+    assert_eq!(batch, golden());
+}
+
+#[test]
+fn remote_over_loopback() {
+    // The test plays the relay
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    let output = temp_path("inputs_remote", "parquet");
+    let mut child = cantelope()
+        .args(["--dbc", &fixture(DBC), "--remote", "-i", &addr])
+        .args(["--cache-ms", "1000", "--output", &output])
+        .spawn()
+        .unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+    for (timestamp, id, data) in read_candump(LOG) {
+        stream
+            .write_all(&relay_frame(timestamp, id, &data))
+            .unwrap();
+    }
+    drop(stream); // Hanging up ends the run
+
+    let status = child.wait().unwrap();
+    assert!(status.success(), "cantelope exited with {}", status);
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+    assert_eq!(batch, golden());
+}
+
+/// Needs the socket feature and a vcan0 (`ip link add dev vcan0 type vcan && ip link set up vcan0`),
+/// otherwise it just says so and passes
+#[cfg(feature = "socket")]
+#[test]
+fn socket_over_vcan() {
+    use common::column;
+    use std::time::Duration;
+
+    if !std::path::Path::new("/sys/class/net/vcan0").exists() {
+        eprintln!("No vcan0, skipping the SocketCAN test");
+        return;
+    }
+
+    let output = temp_path("inputs_socket", "parquet");
+    let mut child = cantelope()
+        .args(["--dbc", &fixture(DBC), "--socket", "-i", "vcan0"])
+        .args(["--cache-ms", "1", "--output", &output]) // A row per frame, so no value gets aggregated away
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(500)); // Let it open the socket
+
+    let status = cantelope()
+        .args(["play", "-i", &fixture(LOG), "--bus", "vcan0"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Ctrl+c on a quiet bus, the receiver notices within its poll interval
+    std::thread::sleep(Duration::from_millis(200));
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = child.wait().unwrap();
+    assert!(status.success(), "cantelope exited with {}", status);
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+
+    // Timestamps come from the kernel, so the rows are different. The values in order have to match.
+    let golden = golden();
+    for field in golden.schema().fields().iter().skip(1) {
+        if field.name().ends_with("_label") {
+            continue;
+        }
+        let values = |b: &RecordBatch| -> Vec<f64> {
+            column(b, field.name()).into_iter().flatten().collect()
+        };
+        assert_eq!(values(&batch), values(&golden), "{}", field.name());
+    }
+}
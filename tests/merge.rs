@@ -0,0 +1,90 @@
+// Several --dbc files merged into one decode database. merge_a.dbc and merge_b.dbc are types.dbc
+// split in two, so the merged run has to log exactly what types.dbc alone does.
+mod common;
+use common::{cantelope, column, fixture, labels, run_candump, run_logger};
+
+#[test]
+fn same_as_one_file() {
+    let golden = run_candump("merge_golden", "types.dbc", "types.log", "1000");
+    let merged = run_logger(
+        "merge_split",
+        &[
+            "--dbc",
+            &fixture("merge_a.dbc"),
+            "--dbc",
+            &fixture("merge_b.dbc"),
+            "--candump",
+            "-i",
+            &fixture("types.log"),
+            "--cache-ms",
+            "1000",
+        ],
+    );
+
+    let types = |batch: &arrow::record_batch::RecordBatch| {
+        batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name().clone(), f.data_type().clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(types(&merged), types(&golden));
+    for field in golden.schema().fields().iter() {
+        let name = field.name();
+        if name.ends_with("_label") {
+            assert_eq!(labels(&merged, name), labels(&golden, name), "{}", name);
+        } else {
+            assert_eq!(column(&merged, name), column(&golden, name), "{}", name);
+        }
+    }
+
+    // Every column says which file it came from
+    let source = |name: &str| {
+        merged
+            .schema()
+            .field_with_name(name)
+            .unwrap()
+            .metadata()
+            .get("dbc_file")
+            .cloned()
+            .unwrap()
+    };
+    for name in ["Rpm", "Mode", "Mode_label"] {
+        assert!(source(name).ends_with("merge_a.dbc"), "{}", name);
+    }
+    for name in ["Voltage", "Pressure"] {
+        assert!(source(name).ends_with("merge_b.dbc"), "{}", name);
+    }
+}
+
+#[test]
+fn conflicts() {
+    // conflict.dbc reuses id 300 and has another Voltage
+    let out = cantelope()
+        .args(["--dbc", &fixture("types.dbc")])
+        .args(["--dbc", &fixture("conflict.dbc"), "--candump"])
+        .args(["-i", &fixture("types.log")])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let (types, conflict) = (fixture("types.dbc"), fixture("conflict.dbc"));
+    for error in [
+        format!(
+            "message id 0x12c (OTHER) is defined in both {} and {}",
+            types, conflict
+        ),
+        format!(
+            "column Voltage would come from both {} (SCALED.Voltage) and {} (EXTRA.Voltage)",
+            types, conflict
+        ),
+    ] {
+        assert!(
+            stderr.contains(&error),
+            "expected {:?} in:\n{}",
+            error,
+            stderr
+        );
+    }
+}
@@ -0,0 +1,38 @@
+mod common;
+use common::{column, run_candump};
+
+#[test]
+fn pages_only_fill_their_own_signals() {
+    let batch = run_candump("mux_pages", "mux.dbc", "mux.log", "100");
+    assert!(batch.num_rows() >= 2);
+
+    // Row 1 is just the page 0 frame
+    assert_eq!(column(&batch, "Page")[0], Some(0.0));
+    assert_eq!(column(&batch, "A")[0], Some(10.0));
+    assert_eq!(column(&batch, "B")[0], None);
+    assert_eq!(column(&batch, "Count")[0], Some(1.0));
+
+    // Row 2 has a page 1 frame, then page 0, then page 1 again. Each signal should
+    // hold the first value from a frame where its page was actually selected.
+    assert_eq!(column(&batch, "Page")[1], Some(1.0));
+    assert_eq!(column(&batch, "B")[1], Some(20.0));
+    assert_eq!(column(&batch, "A")[1], Some(30.0));
+    assert_eq!(column(&batch, "Count")[1], Some(2.0));
+}
+
+#[test]
+fn extended_multiplexing_follows_nested_switches() {
+    let batch = run_candump("mux_ext", "mux.dbc", "mux_ext.log", "10");
+    assert!(batch.num_rows() >= 3);
+
+    let sub = column(&batch, "SubSw");
+    let c = column(&batch, "C");
+    let d = column(&batch, "D");
+
+    // Sw=1, SubSw=2: C is selected
+    assert_eq!((sub[0], c[0], d[0]), (Some(2.0), Some(50.0), None));
+    // Sw=1, SubSw=5: outside C's 2-3 range
+    assert_eq!((sub[1], c[1], d[1]), (Some(5.0), None, None));
+    // Sw=0: SubSw itself is off, so nothing under it counts either
+    assert_eq!((sub[2], c[2], d[2]), (None, None, Some(70.0)));
+}
@@ -0,0 +1,124 @@
+// `play`: which frames go out, on which interface and with which id. The logger listens on vcan0
+// and whatever it decodes is what got sent. Needs the socket feature, and a vcan0
+// (`ip link add dev vcan0 type vcan && ip link set up vcan0`) for everything that actually sends.
+#![cfg(feature = "socket")]
+mod common;
+use common::{cantelope, column, fixture, read_parquet, relay_frame, temp_path};
+
+use arrow::record_batch::RecordBatch;
+use std::time::Duration;
+
+/// Logs vcan0 with types.dbc while `play` runs with these arguments. None if there's no vcan0.
+fn logged_on_vcan0(test: &str, play: &[&str]) -> Option<RecordBatch> {
+    if !std::path::Path::new("/sys/class/net/vcan0").exists() {
+        eprintln!("No vcan0, skipping {}", test);
+        return None;
+    }
+
+    let output = temp_path(test, "parquet");
+    let mut child = cantelope()
+        .args(["--dbc", &fixture("types.dbc"), "--socket", "-i", "vcan0"])
+        .args(["--cache-ms", "1", "--output", &output])
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(500)); // Let it open the socket
+
+    let status = cantelope()
+        .arg("play")
+        .args(play)
+        .args(["--speed", "max"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Ctrl+c on a quiet bus, the receiver notices within its poll interval
+    std::thread::sleep(Duration::from_millis(200));
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = child.wait().unwrap();
+    assert!(status.success(), "cantelope exited with {}", status);
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+    Some(batch)
+}
+
+/// Values a column got, leaving out the rows it wasn't in
+fn values(batch: &RecordBatch, name: &str) -> Vec<f64> {
+    column(batch, name).into_iter().flatten().collect()
+}
+
+#[test]
+fn routing_and_filters() {
+    // The can1 frame isn't routed anywhere, SIGNED is skipped and SCALED goes out as BIGEND
+    let log = temp_path("play_routing", "log");
+    std::fs::write(
+        &log,
+        "\
+(1700000000.000000) can0 12C#1234FFB000000000
+(1700000000.000000) can1 12C#0BB8005000000000
+(1700000000.000000) can0 12D#38FF1D0200000000
+(1700000000.000000) can0 12E#AA9B2A0000000000
+",
+    )
+    .unwrap();
+    let batch = logged_on_vcan0(
+        "play_routing",
+        &[
+            "-i",
+            &log,
+            "--bus",
+            "vcan0=can0",
+            "--skip",
+            "12D",
+            "--remap",
+            "12E=12C",
+        ],
+    );
+    _ = std::fs::remove_file(&log);
+    let Some(batch) = batch else {
+        return;
+    };
+    assert_eq!(values(&batch, "Rpm"), vec![4660.0, 0xAA9B as f64]);
+    assert!(values(&batch, "Current").is_empty());
+    assert!(values(&batch, "Voltage").is_empty());
+}
+
+/// A raw archive played with --bus vcan0=can0 has no interface to match, it all goes to vcan0
+#[test]
+fn raw_archive_one_bus() {
+    let archive = temp_path("play_raw", "bin");
+    std::fs::write(&archive, relay_frame(1700000000.0, 0x12C, &[0x12, 0x34])).unwrap();
+    let batch = logged_on_vcan0("play_raw", &["-i", &archive, "--bus", "vcan0=can0"]);
+    _ = std::fs::remove_file(&archive);
+    let Some(batch) = batch else {
+        return;
+    };
+    assert_eq!(values(&batch, "Rpm"), vec![4660.0]);
+}
+
+/// With several --bus there's no telling which one a raw archive's frames belong on.
+/// Fails before opening any interface, so this one doesn't need vcan0.
+#[test]
+fn raw_archive_several_buses() {
+    let archive = temp_path("play_raw_buses", "bin");
+    std::fs::write(&archive, relay_frame(1700000000.0, 0x12C, &[0x12, 0x34])).unwrap();
+    let out = cantelope()
+        .args(["play", "-i", &archive, "--speed", "max"])
+        .args(["--bus", "vcan0=can0", "--bus", "vcan1=can1"])
+        .output()
+        .unwrap();
+    _ = std::fs::remove_file(&archive);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains(
+            "Raw archives don't record the interface, so they can only go out on one --bus"
+        ),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,67 @@
+// --watch-dbc: a DBC that changes mid-run finishes the current file and carries on in the next
+// segment with the new columns. Goes through --stdin so the test decides when frames arrive.
+mod common;
+use common::{cantelope, column, fixture, read_parquet, temp_path};
+
+use std::io::Write;
+use std::process::Stdio;
+use std::thread::sleep;
+use std::time::Duration;
+
+#[test]
+fn new_segment() {
+    let dbc = temp_path("reload", "dbc");
+    let original = std::fs::read_to_string(fixture("types.dbc")).unwrap();
+    std::fs::write(&dbc, &original).unwrap();
+    let output = temp_path("reload", "parquet");
+    let segment = output.replace(".parquet", ".1.parquet");
+
+    let mut child = cantelope()
+        .args([
+            "--dbc",
+            &dbc,
+            "--stdin",
+            "--watch-dbc",
+            "--cache-ms",
+            "1000",
+        ])
+        .args(["--output", &output])
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let log = std::fs::read_to_string(fixture("types.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+
+    // The frames at t=0 with the old DBC, then a rename, then the rest. The watcher looks
+    // at most once a second, and only when a frame comes in.
+    for line in &lines[..4] {
+        writeln!(stdin, "{}", line).unwrap();
+    }
+    stdin.flush().unwrap();
+    sleep(Duration::from_millis(500));
+    std::fs::write(&dbc, original.replace("SG_ Rpm ", "SG_ EngineSpeed ")).unwrap();
+    sleep(Duration::from_millis(1500));
+    for line in &lines[4..] {
+        writeln!(stdin, "{}", line).unwrap();
+    }
+    drop(stdin);
+    let status = child.wait().unwrap();
+    assert!(status.success(), "cantelope exited with {}", status);
+
+    let before = read_parquet(&output);
+    let after = read_parquet(&segment);
+    for path in [&dbc, &output, &segment] {
+        _ = std::fs::remove_file(path);
+    }
+
+    assert_eq!(column(&before, "Rpm"), vec![Some(4660.0)]);
+    assert!(before.column_by_name("EngineSpeed").is_none());
+    assert!(after.column_by_name("Rpm").is_none());
+    assert_eq!(
+        column(&after, "EngineSpeed").last(),
+        Some(&Some(3000.0)),
+        "{:?}",
+        after
+    );
+}
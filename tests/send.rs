@@ -0,0 +1,67 @@
+// `send` and the logger back to back: the frame send builds, served over TCP to a --remote logger,
+// has to decode to the values it was given.
+mod common;
+use common::{cantelope, column, fixture, run_logger};
+
+use std::io::{BufRead, BufReader, Read};
+use std::net::TcpListener;
+use std::process::Stdio;
+
+/// Sends one frame of `message` with these values and returns what the logger made of it
+fn round_trip(test: &str, message: &str, values: &[&str]) -> arrow::record_batch::RecordBatch {
+    // A port nothing else has, free again by the time send binds it
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+        .to_string();
+    let mut send = cantelope()
+        .args(["send", "--dbc", &fixture("types.dbc"), message])
+        .args(values)
+        .args(["--serve", &port])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // "Waiting for a client" means it's listening. Kept open, send prints the frame later.
+    let mut stdout = BufReader::new(send.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert!(line.starts_with("Waiting for a client"), "{}", line);
+
+    // send quits after the frame, and the hang-up ends the logger's run
+    let batch = run_logger(
+        test,
+        &[
+            "--dbc",
+            &fixture("types.dbc"),
+            "--remote",
+            "-i",
+            &format!("127.0.0.1:{}", port),
+            "--cache-ms",
+            "1000",
+        ],
+    );
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let status = send.wait().unwrap();
+    assert!(status.success(), "send exited with {}", status);
+    batch
+}
+
+#[test]
+fn integer_signals() {
+    let batch = round_trip("send_integer", "BIGEND", &["Rpm=4660", "Temp=-10.5"]);
+    assert_eq!(column(&batch, "Rpm"), vec![Some(4660.0)]);
+    let temp = column(&batch, "Temp")[0].unwrap();
+    assert!((temp + 10.5).abs() < 1e-9, "{}", temp);
+}
+
+#[test]
+fn float_signals() {
+    // Gain has factor 2 and offset 1, so -0.5 goes out as the float -0.75
+    let batch = round_trip("send_float", "FLOATS", &["Pressure=1.5", "Gain=-0.5"]);
+    assert_eq!(column(&batch, "Pressure"), vec![Some(1.5)]);
+    assert_eq!(column(&batch, "Gain"), vec![Some(-0.5)]);
+}
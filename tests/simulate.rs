@@ -0,0 +1,87 @@
+mod common;
+use common::{cantelope, column, fixture, read_parquet, temp_path};
+
+use arrow::record_batch::RecordBatch;
+
+fn simulate(test: &str, seed: &str) -> RecordBatch {
+    let output = temp_path(test, "parquet");
+    let status = cantelope()
+        .args([
+            "--dbc",
+            &fixture("mux.dbc"),
+            "--simulate",
+            "-i",
+            &fixture("sim.txt"),
+        ])
+        .args(["--seed", seed, "--speed", "max", "--end", "2"])
+        .args(["--cache-ms", "100", "--output", &output])
+        .status()
+        .expect("failed to run cantelope");
+    assert!(status.success(), "cantelope exited with {}", status);
+
+    let batch = read_parquet(&output);
+    _ = std::fs::remove_file(&output);
+    batch
+}
+
+#[test]
+fn generators_drive_the_signals() {
+    let batch = simulate("sim_generators", "7");
+    // 100 ms default cycle time over 2 s
+    assert!(batch.num_rows() >= 15);
+
+    let a: Vec<f64> = column(&batch, "A").into_iter().flatten().collect();
+    assert!(!a.is_empty());
+    assert!(a.iter().all(|v| *v == 5.0));
+
+    // Ramp goes 0..100 over a second, then starts over
+    let count: Vec<f64> = column(&batch, "Count").into_iter().flatten().collect();
+    assert!(count.windows(2).any(|w| w[1] > w[0]));
+    assert!(count.windows(2).any(|w| w[1] < w[0]));
+    assert!(count.iter().all(|v| (0.0..100.0).contains(v)));
+
+    // SubSw wanders between 2 and 3, which is always C's page
+    let sub: Vec<f64> = column(&batch, "SubSw").into_iter().flatten().collect();
+    assert!(sub.iter().all(|v| (2.0..=3.0).contains(v)));
+    assert!(column(&batch, "C").iter().any(|v| v.is_some()));
+    assert!(column(&batch, "D").iter().all(|v| v.is_none()));
+}
+
+#[test]
+fn same_seed_same_frames() {
+    let first = simulate("sim_seed_a", "42");
+    let second = simulate("sim_seed_b", "42");
+    assert_eq!(first.num_rows(), second.num_rows());
+    for name in ["SubSw", "C", "Count"] {
+        assert_eq!(column(&first, name), column(&second, name), "{}", name);
+    }
+}
+
+#[test]
+fn unknown_signal() {
+    // A typo shouldn't quietly leave the signal at 0
+    let generators = temp_path("sim_unknown", "txt");
+    std::fs::write(&generators, "A = const(5)\nCuont = ramp(0, 100, 1000)\n").unwrap();
+    let out = cantelope()
+        .args([
+            "--dbc",
+            &fixture("mux.dbc"),
+            "--simulate",
+            "-i",
+            &generators,
+        ])
+        .args(["--speed", "max", "--end", "1"])
+        .args(["--output", &temp_path("sim_unknown", "parquet")])
+        .output()
+        .unwrap();
+    _ = std::fs::remove_file(&generators);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let error = format!("{}:2: Cuont isn't a signal in the DBC", generators);
+    assert!(
+        stderr.contains(&error),
+        "expected {:?} in:\n{}",
+        error,
+        stderr
+    );
+}